@@ -1,81 +1,342 @@
 use util::Grid;
-use std::collections::{HashSet, VecDeque};
 
 const TILE_N: u8 = 0b0001;
 const TILE_E: u8 = 0b0010;
 const TILE_S: u8 = 0b0100;
 const TILE_W: u8 = 0b1000;
+const DIRS: [(u8, (i32, i32)); 4] = [(TILE_N, (0, -1)), (TILE_E, (1, 0)), (TILE_S, (0, 1)), (TILE_W, (-1, 0))];
 
 fn invert_tile(t: u8) -> u8 {
 	((t << 2) & 0xf) | (t >> 2)
 }
 
-fn main() {
-	let input = include_bytes!("../inputs/day10.txt");
-	let mut start = None;
-	let mut grid = Grid::load_from_bytes(input, |c, x, y| match c {
-		b'.' => 0,
-		b'|' => TILE_N | TILE_S,
-		b'-' => TILE_E | TILE_W,
-		b'L' => TILE_N | TILE_E,
-		b'J' => TILE_N | TILE_W,
-		b'7' => TILE_S | TILE_W,
-		b'F' => TILE_E | TILE_S,
-		b'S' => {
-			assert!(start.is_none());
-			start = Some((x, y));
-			0
-		},
-		_ => unreachable!()
-	});
-	let (width, height) = (grid.width, grid.height);
-	let start = start.unwrap();
-	let (start_x, start_y) = start;
-	
-	grid[start] = invert_tile(
-		  (grid[(start_x, start_y-1)] & TILE_S)
-		| (grid[(start_x+1, start_y)] & TILE_W)
-		| (grid[(start_x, start_y+1)] & TILE_N)
-		| (grid[(start_x-1, start_y)] & TILE_E)
-	);
-	
-	let mut loop_tiles = HashSet::new();
-	let mut queue = VecDeque::new();
-	queue.push_back(start);
-	loop_tiles.insert(start);
-	while let Some((x, y)) = queue.pop_front() {
-		let tile = grid[(x,y)];
-		let mut neighbors = Vec::with_capacity(4);
-		if tile & TILE_N != 0 { neighbors.push((x, y-1)) }
-		if tile & TILE_E != 0 { neighbors.push((x+1, y)) }
-		if tile & TILE_S != 0 { neighbors.push((x, y+1)) }
-		if tile & TILE_W != 0 { neighbors.push((x-1, y)) }
-		for neigh in neighbors {
-			if !loop_tiles.contains(&neigh) {
-				loop_tiles.insert(neigh);
-				queue.push_back(neigh);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tile {
+	Ground,
+	Vert,
+	Horiz,
+	NE,
+	NW,
+	SW,
+	SE,
+	Start,
+}
+impl Tile {
+	const PIPES: [Tile; 6] = [Tile::Vert, Tile::Horiz, Tile::NE, Tile::NW, Tile::SW, Tile::SE];
+	fn from_byte(c: u8) -> Option<Tile> {
+		Some(match c {
+			b'.' => Tile::Ground,
+			b'|' => Tile::Vert,
+			b'-' => Tile::Horiz,
+			b'L' => Tile::NE,
+			b'J' => Tile::NW,
+			b'7' => Tile::SW,
+			b'F' => Tile::SE,
+			b'S' => Tile::Start,
+			_ => return None,
+		})
+	}
+	// The start tile has no connections of its own until its shape is resolved
+	fn connections(self) -> u8 {
+		match self {
+			Tile::Ground | Tile::Start => 0,
+			Tile::Vert => TILE_N | TILE_S,
+			Tile::Horiz => TILE_E | TILE_W,
+			Tile::NE => TILE_N | TILE_E,
+			Tile::NW => TILE_N | TILE_W,
+			Tile::SW => TILE_S | TILE_W,
+			Tile::SE => TILE_E | TILE_S,
+		}
+	}
+}
+
+#[derive(Debug)]
+enum MazeError {
+	UnknownTile(char, (i32, i32)),
+	NoStart,
+	// no shape of the start tile closes a loop
+	NoLoop,
+	// several shapes of the start tile close a loop
+	AmbiguousStart(Vec<Tile>),
+}
+impl std::fmt::Display for MazeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			MazeError::UnknownTile(c, (x, y)) => write!(f, "unknown tile {:?} at ({}, {})", c, x, y),
+			MazeError::NoStart => write!(f, "no start tile"),
+			MazeError::NoLoop => write!(f, "start tile is not part of a loop"),
+			MazeError::AmbiguousStart(shapes) => write!(f, "start tile could be any of {:?}", shapes),
+		}
+	}
+}
+
+// Each start tile is resolved on its own, so a loop can't go through another start tile
+struct Maze {
+	grid: Grid<Tile>,
+	// in reading order
+	starts: Vec<(i32, i32)>,
+}
+impl Maze {
+	fn parse(input: &[u8]) -> Result<Maze, MazeError> {
+		let mut starts = vec![];
+		let mut unknown = None;
+		let grid = Grid::load_from_bytes(input, |c, x, y| {
+			let tile = Tile::from_byte(c).unwrap_or_else(|| {
+				unknown.get_or_insert(MazeError::UnknownTile(c as char, (x, y)));
+				Tile::Ground
+			});
+			if tile == Tile::Start {
+				starts.push((x, y));
 			}
+			tile
+		});
+		if let Some(err) = unknown {
+			return Err(err);
 		}
+		if starts.is_empty() {
+			return Err(MazeError::NoStart);
+		}
+		starts.sort_by_key(|(x, y)| (*y, *x));
+		Ok(Maze { grid, starts })
+	}
+
+	// Out-of-bounds tiles have no connections
+	fn connections(&self, pos: (i32, i32)) -> u8 {
+		self.grid.get(pos).map(|t| t.connections()).unwrap_or(0)
 	}
-	let loop_len = loop_tiles.len();
-	
-	println!("part 1: {}", loop_len/2);
-	
-	let mut enclosed_cnt = 0;
+
+	// Follows the pipes from a start tile, assuming the given shape for it.
+	// Returns the loop as an ordered cycle of positions, beginning with the start.
+	fn follow_loop(&self, start: (i32, i32), start_shape: Tile) -> Option<Vec<(i32, i32)>> {
+		let mut path = vec![start];
+		let mut pos = start;
+		let mut tile = start_shape.connections();
+		let mut came_from = 0;
+		loop {
+			let (dir, (dx, dy)) = DIRS.into_iter().find(|(dir, _)| tile & dir != 0 && *dir != came_from)?;
+			pos = (pos.0 + dx, pos.1 + dy);
+			came_from = invert_tile(dir);
+			if pos == start {
+				return if start_shape.connections() & came_from != 0 { Some(path) } else { None };
+			}
+			tile = self.connections(pos);
+			if tile & came_from == 0 {
+				return None;
+			}
+			path.push(pos);
+		}
+	}
+
+	fn find_loop(&self, start: (i32, i32)) -> Result<(Tile, Vec<(i32, i32)>), MazeError> {
+		let (sx, sy) = start;
+		// directions in which a neighbor connects back to the start
+		let mut candidates = 0;
+		for (dir, (dx, dy)) in DIRS {
+			if self.connections((sx + dx, sy + dy)) & invert_tile(dir) != 0 {
+				candidates |= dir;
+			}
+		}
+		let mut loops: Vec<_> = Tile::PIPES.into_iter()
+			.filter(|shape| shape.connections() & candidates == shape.connections())
+			.filter_map(|shape| self.follow_loop(start, shape).map(|path| (shape, path)))
+			.collect();
+		match loops.len() {
+			0 => Err(MazeError::NoLoop),
+			1 => Ok(loops.pop().unwrap()),
+			_ => Err(MazeError::AmbiguousStart(loops.into_iter().map(|(shape, _)| shape).collect())),
+		}
+	}
+}
+
+// Counts tiles strictly inside the loop: shoelace formula for the area
+// of the polygon through the tile centers, then Pick's theorem
+fn enclosed_by_area(path: &[(i32, i32)]) -> i64 {
+	let mut area2 = 0;
+	for (i, (x1, y1)) in path.iter().copied().enumerate() {
+		let (x2, y2) = path[(i + 1) % path.len()];
+		area2 += x1 as i64 * y2 as i64 - x2 as i64 * y1 as i64;
+	}
+	let area2 = area2.abs();
+	// A = I + B/2 - 1
+	(area2 - path.len() as i64) / 2 + 1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+	Loop,
+	// enclosed by the loop, whether ground or stray pipe
+	Inside,
+	Outside,
+	// stray pipe outside the loop
+	Junk,
+}
+
+// Classifies tiles by flood-filling from outside the loop, in a grid upscaled 3x
+// so that the fill can squeeze between adjacent pipes
+fn classify_by_flood_fill(maze: &Maze, start_shape: Tile, path: &[(i32, i32)]) -> Grid<Region> {
+	let (width, height) = (maze.grid.width, maze.grid.height);
+	let mut on_loop = Grid::new(width, height, false);
+	let mut walls = Grid::new(width*3, height*3, false);
+	for (i, (x, y)) in path.iter().copied().enumerate() {
+		on_loop[(x, y)] = true;
+		let (cx, cy) = (x*3 + 1, y*3 + 1);
+		walls[(cx, cy)] = true;
+		let tile = if i == 0 { start_shape.connections() } else { maze.connections((x, y)) };
+		for (dir, (dx, dy)) in DIRS {
+			if tile & dir != 0 {
+				walls[(cx + dx, cy + dy)] = true;
+			}
+		}
+	}
+
+	// tile corners are never walls, so the border of the upscaled grid is all outside
+	let mut outside = Grid::new(width*3, height*3, false);
+	let mut stack = vec![(0, 0)];
+	outside[(0, 0)] = true;
+	while let Some((x, y)) = stack.pop() {
+		for (_, (dx, dy)) in DIRS {
+			let neigh = (x + dx, y + dy);
+			if walls.get(neigh) == Some(&false) && !outside[neigh] {
+				outside[neigh] = true;
+				stack.push(neigh);
+			}
+		}
+	}
+
+	let mut regions = Grid::new(width, height, Region::Outside);
 	for y in 0..height {
-		let mut top_inside = false;
-		let mut bottom_inside = false;
 		for x in 0..width {
-			if loop_tiles.contains(&(x, y)) {
-				let tile = grid[(x, y)];
-				if tile & TILE_N != 0 { top_inside = !top_inside; }
-				if tile & TILE_S != 0 { bottom_inside = !bottom_inside; }
-			} else if top_inside || bottom_inside {
-				assert!(top_inside && bottom_inside);
-				enclosed_cnt += 1;
+			regions[(x, y)] = if on_loop[(x, y)] {
+				Region::Loop
+			} else if !outside[(x*3 + 1, y*3 + 1)] {
+				Region::Inside
+			} else if maze.grid[(x, y)] != Tile::Ground {
+				Region::Junk
+			} else {
+				Region::Outside
+			};
+		}
+	}
+	regions
+}
+
+fn render(maze: &Maze, start: (i32, i32), start_shape: Tile, regions: &Grid<Region>) -> String {
+	let mut out = String::new();
+	for y in 0..regions.height {
+		for x in 0..regions.width {
+			out.push(match regions[(x, y)] {
+				Region::Loop => {
+					let tile = if (x, y) == start { start_shape } else { maze.grid[(x, y)] };
+					match tile {
+						Tile::Vert => '│',
+						Tile::Horiz => '─',
+						Tile::NE => '└',
+						Tile::NW => '┘',
+						Tile::SW => '┐',
+						Tile::SE => '┌',
+						Tile::Ground | Tile::Start => unreachable!(),
+					}
+				},
+				Region::Inside => '█',
+				Region::Outside => ' ',
+				Region::Junk => '·',
+			});
+		}
+		out.push('\n');
+	}
+	out
+}
+
+fn main() {
+	let input = include_bytes!("../inputs/day10.txt");
+	let maze = match Maze::parse(input) {
+		Ok(maze) => maze,
+		Err(err) => {
+			println!("{}", err);
+			return;
+		}
+	};
+
+	let render_regions = std::env::args().any(|arg| arg == "--render");
+	for start in maze.starts.iter().copied() {
+		// a single start gives the puzzle's answers, several are reported one after the other
+		if maze.starts.len() > 1 {
+			println!("start at ({}, {}):", start.0, start.1);
+		}
+		let (start_shape, path) = match maze.find_loop(start) {
+			Ok(found) => found,
+			Err(err) => {
+				println!("{}", err);
+				continue;
 			}
+		};
+
+		println!("part 1: {}", path.len()/2);
+
+		if render_regions {
+			let regions = classify_by_flood_fill(&maze, start_shape, &path);
+			print!("{}", render(&maze, start, start_shape, &regions));
+		}
+		println!("part 2: {}", enclosed_by_area(&path));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Counts tiles strictly inside the loop by scanning each row and tracking
+	// separately whether the top and bottom halves of the current tile are inside
+	fn enclosed_by_scanline(maze: &Maze, start_shape: Tile, path: &[(i32, i32)]) -> i64 {
+		let mut on_loop = Grid::new(maze.grid.width, maze.grid.height, false);
+		for pos in path {
+			on_loop[*pos] = true;
+		}
+		let mut enclosed_cnt = 0;
+		for y in 0..maze.grid.height {
+			let mut top_inside = false;
+			let mut bottom_inside = false;
+			for x in 0..maze.grid.width {
+				if on_loop[(x, y)] {
+					let tile = if (x, y) == path[0] { start_shape.connections() } else { maze.connections((x, y)) };
+					if tile & TILE_N != 0 { top_inside = !top_inside; }
+					if tile & TILE_S != 0 { bottom_inside = !bottom_inside; }
+				} else if top_inside || bottom_inside {
+					assert!(top_inside && bottom_inside);
+					enclosed_cnt += 1;
+				}
+			}
+		}
+		enclosed_cnt
+	}
+
+	#[test]
+	fn multiple_starts() {
+		let maze = Maze::parse(b"S-7.F-S\n|.|.|.|\nL-J.L-J\n.......\n..F-...\n..S....").unwrap();
+		assert_eq!(maze.starts, [(0, 0), (6, 0), (2, 5)]);
+		let (shape, path) = maze.find_loop((0, 0)).unwrap();
+		assert_eq!((shape, path.len()), (Tile::SE, 8));
+		assert_eq!(enclosed_by_area(&path), 1);
+		let (shape, path) = maze.find_loop((6, 0)).unwrap();
+		assert_eq!((shape, path.len()), (Tile::SW, 8));
+		assert!(matches!(maze.find_loop((2, 5)), Err(MazeError::NoLoop)));
+	}
+
+	// the statement's part 2 examples, including pipes to squeeze between
+	#[test]
+	fn enclosed_counts_agree() {
+		for (input, expected) in [
+			(&include_bytes!("../inputs/day10_ex1.txt")[..], 4),
+			(&include_bytes!("../inputs/day10_ex2.txt")[..], 4),
+			(&include_bytes!("../inputs/day10_ex3.txt")[..], 8),
+			(&include_bytes!("../inputs/day10_ex4.txt")[..], 10),
+		] {
+			let maze = Maze::parse(input).unwrap();
+			let (start_shape, path) = maze.find_loop(maze.starts[0]).unwrap();
+			let regions = classify_by_flood_fill(&maze, start_shape, &path);
+			assert_eq!(enclosed_by_area(&path), expected);
+			assert_eq!(enclosed_by_scanline(&maze, start_shape, &path), expected);
+			assert_eq!(regions.data.iter().filter(|r| **r == Region::Inside).count(), expected as usize);
 		}
 	}
-	
-	println!("part 2: {}", enclosed_cnt);
 }
\ No newline at end of file
@@ -10,44 +10,197 @@ fn compute_offsets(empty: &[bool]) -> Vec<usize> {
 	offsets
 }
 
-fn compute_distance_sum(
-		pts: &[(usize, usize)],
-		x_off: &[usize], y_off: &[usize],
-		off_mult: usize
-) -> usize {
+// Position after expansion, where each empty row/column becomes `factor` rows/columns
+fn expand(coord: usize, offsets: &[usize], factor: u128) -> u128 {
+	let empty_before = offsets[coord];
+	(coord - empty_before) as u128 + empty_before as u128 * factor
+}
+
+// Sum of pairwise distances along one axis: once sorted, each coordinate
+// is at distance c_i - c_j from each of the i coordinates before it
+fn axis_distance_sum(mut coords: Vec<u128>) -> u128 {
+	coords.sort_unstable();
 	let mut dist_sum = 0;
-	for (i, (x1, y1)) in pts.iter().enumerate() {
-		let (x1, y1) = (x1 + x_off[*x1]*off_mult, y1 + y_off[*y1]*off_mult);
-		for (x2, y2) in &pts[0..i] {
-			let (x2, y2) = (x2 + x_off[*x2]*off_mult, y2 + y_off[*y2]*off_mult);
-			dist_sum += x2.abs_diff(x1) + y2.abs_diff(y1);
-		}
+	let mut prefix_sum = 0;
+	for (i, c) in coords.into_iter().enumerate() {
+		dist_sum += c * i as u128 - prefix_sum;
+		prefix_sum += c;
 	}
 	dist_sum
 }
 
-fn main() {
-	let input = include_str!("../inputs/day11.txt");
-	
-	let width = input.lines().next().unwrap().len();
-	let height = (input.len() + 1) / (width + 1);
-	let mut empty_cols = vec![true; width];
-	let mut empty_rows = vec![true; height];
-	
-	let mut galaxies = vec![];
-	for (y, line) in input.lines().enumerate() {
-		for (x, c) in line.chars().enumerate() {
-			if c == '#' {
-				galaxies.push((x, y));
-				empty_cols[x] = false;
-				empty_rows[y] = false;
+struct Image {
+	galaxies: Vec<(usize, usize)>,
+	col_offset: Vec<usize>,
+	row_offset: Vec<usize>,
+}
+impl Image {
+	fn parse(input: &str) -> Image {
+		let width = input.lines().next().unwrap().len();
+		let height = (input.len() + 1) / (width + 1);
+		let mut empty_cols = vec![true; width];
+		let mut empty_rows = vec![true; height];
+
+		let mut galaxies = vec![];
+		for (y, line) in input.lines().enumerate() {
+			for (x, c) in line.chars().enumerate() {
+				if c == '#' {
+					galaxies.push((x, y));
+					empty_cols[x] = false;
+					empty_rows[y] = false;
+				}
 			}
 		}
+
+		let col_offset = compute_offsets(&empty_cols);
+		let row_offset = compute_offsets(&empty_rows);
+		Image { galaxies, col_offset, row_offset }
+	}
+	fn expand(&self, factor: u128) -> Universe {
+		let galaxies = self.galaxies.iter()
+			.map(|(x, y)| (expand(*x, &self.col_offset, factor), expand(*y, &self.row_offset, factor)))
+			.collect();
+		Universe { galaxies }
+	}
+}
+
+// Galaxies are numbered from 1, in reading order, as in the puzzle statement
+struct Universe {
+	galaxies: Vec<(u128, u128)>,
+}
+impl Universe {
+	fn distance_sum(&self) -> u128 {
+		axis_distance_sum(self.galaxies.iter().map(|(x, _)| *x).collect())
+			+ axis_distance_sum(self.galaxies.iter().map(|(_, y)| *y).collect())
+	}
+	// None if there is no galaxy with that id
+	fn galaxy(&self, g: usize) -> Option<(u128, u128)> {
+		self.galaxies.get(g.checked_sub(1)?).copied()
+	}
+	fn distance(&self, g1: usize, g2: usize) -> Option<u128> {
+		let (x1, y1) = self.galaxy(g1)?;
+		let (x2, y2) = self.galaxy(g2)?;
+		Some(x1.abs_diff(x2) + y1.abs_diff(y2))
+	}
+	// The k galaxies closest to g, with their distances, closest first
+	fn nearest(&self, g: usize, k: usize) -> Option<Vec<(usize, u128)>> {
+		let (x, y) = self.galaxy(g)?;
+		let mut others: Vec<(usize, u128)> = self.galaxies.iter().enumerate()
+			.filter(|(i, _)| i + 1 != g)
+			.map(|(i, (x2, y2))| (i + 1, x.abs_diff(*x2) + y.abs_diff(*y2)))
+			.collect();
+		if k < others.len() {
+			others.select_nth_unstable_by_key(k, |(g2, dist)| (*dist, *g2));
+			others.truncate(k);
+		}
+		others.sort_unstable_by_key(|(g2, dist)| (*dist, *g2));
+		Some(others)
+	}
+	// In the rotated coordinates u = x+y, v = x-y, Manhattan distance is
+	// max(|du|, |dv|), so the farthest pair is found among the extremes of u and v
+	fn farthest_pair(&self) -> Option<(usize, usize, u128)> {
+		if self.galaxies.len() < 2 {
+			return None;
+		}
+		let max_y = self.galaxies.iter().map(|(_, y)| *y).max().unwrap();
+		let u = |g: &usize| { let (x, y) = self.galaxies[g - 1]; x + y };
+		let v = |g: &usize| { let (x, y) = self.galaxies[g - 1]; x + (max_y - y) };
+		let ids = 1..=self.galaxies.len();
+		[
+			(ids.clone().min_by_key(u).unwrap(), ids.clone().max_by_key(u).unwrap()),
+			(ids.clone().min_by_key(v).unwrap(), ids.clone().max_by_key(v).unwrap()),
+		].into_iter()
+			.map(|(g1, g2)| (g1.min(g2), g1.max(g2), self.distance(g1, g2).unwrap()))
+			.max_by_key(|(_, _, dist)| *dist)
+	}
+}
+
+fn main() {
+	let image = Image::parse(include_str!("../inputs/day11.txt"));
+	println!("part 1: {}", image.expand(2).distance_sum());
+	println!("part 2: {}", image.expand(1_000_000).distance_sum());
+
+	let mut args = std::env::args().skip_while(|arg| arg != "--expansion").skip(1);
+	let factor = args.next().map(|factor| factor.parse::<u128>().expect("invalid expansion factor"));
+	if let Some(factor) = factor {
+		println!("factor {}: {}", factor, image.expand(factor).distance_sum());
+	}
+
+	// Queries on the universe expanded by the given factor, or as in part 1
+	let universe = image.expand(factor.unwrap_or(2));
+	let query = |name: &str| -> Option<[usize; 2]> {
+		std::env::args().any(|arg| arg == name).then(|| {
+			let nums: Vec<usize> = std::env::args().skip_while(|arg| arg != name).skip(1).take(2)
+				.map(|n| n.parse().expect("invalid number")).collect();
+			nums.try_into().unwrap_or_else(|_| panic!("{} takes two numbers", name))
+		})
+	};
+	if let Some([g1, g2]) = query("--distance") {
+		match universe.distance(g1, g2) {
+			Some(dist) => println!("distance between {} and {}: {}", g1, g2, dist),
+			None => println!("no such galaxy"),
+		}
+	}
+	if let Some([g, k]) = query("--nearest") {
+		match universe.nearest(g, k) {
+			Some(nearest) => for (g2, dist) in nearest {
+				println!("{}: {}", g2, dist);
+			},
+			None => println!("no such galaxy"),
+		}
+	}
+	if std::env::args().any(|arg| arg == "--farthest") {
+		if let Some((g1, g2, dist)) = universe.farthest_pair() {
+			println!("farthest pair: {} and {}, at {}", g1, g2, dist);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn example() -> Image {
+		Image::parse(include_str!("../inputs/day11_ex.txt"))
+	}
+
+	#[test]
+	fn distance_sums() {
+		for (factor, dist_sum) in [(2, 374), (10, 1030), (100, 8410)] {
+			assert_eq!(example().expand(factor).distance_sum(), dist_sum);
+		}
+	}
+
+	// the distances given in the puzzle statement
+	#[test]
+	fn distances() {
+		let universe = example().expand(2);
+		for (g1, g2, dist) in [(5, 9, 9), (1, 7, 15), (3, 6, 17), (8, 9, 5)] {
+			assert_eq!(universe.distance(g1, g2), Some(dist));
+		}
+		assert_eq!(universe.distance(0, 1), None);
+		assert_eq!(universe.distance(1, 10), None);
+	}
+
+	#[test]
+	fn nearest() {
+		let universe = example().expand(2);
+		let ids = 1..=universe.galaxies.len();
+		for g in ids.clone() {
+			let mut dists: Vec<u128> = ids.clone().filter(|g2| *g2 != g).map(|g2| universe.distance(g, g2).unwrap()).collect();
+			dists.sort();
+			assert!(universe.nearest(g, 3).unwrap().into_iter().map(|(_, dist)| dist).eq(dists[..3].iter().copied()));
+		}
+		assert_eq!(universe.nearest(0, 3), None);
+		assert_eq!(universe.nearest(10, 3), None);
+	}
+
+	#[test]
+	fn farthest_pair() {
+		let universe = example().expand(2);
+		let ids = 1..=universe.galaxies.len();
+		let max_dist = ids.clone().flat_map(|g1| ids.clone().map(move |g2| (g1, g2)))
+			.map(|(g1, g2)| universe.distance(g1, g2).unwrap()).max();
+		assert_eq!(universe.farthest_pair().map(|(_, _, dist)| dist), max_dist);
 	}
-	
-	let col_offset = compute_offsets(&empty_cols);
-	let row_offset = compute_offsets(&empty_rows);
-	
-	println!("part 1: {}", compute_distance_sum(&galaxies, &col_offset, &row_offset, 1));
-	println!("part 2: {}", compute_distance_sum(&galaxies, &col_offset, &row_offset, 999_999));
 }
\ No newline at end of file
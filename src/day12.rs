@@ -58,6 +58,49 @@ impl<'a> Solver<'a> {
 		}
 	}
 	
+	// Writes the arrangement of the given rank (in the order the arrangements are counted)
+	// into row, which must be initialized to '.'. rank must be less than the count.
+	fn unrank_from(&mut self, mut col_off: u32, grp_off: u32, mut rank: u64, row: &mut [u8]) {
+		if grp_off == self.groups.len() as u32 {
+			return;
+		}
+		col_off += (self.pot_mask >> col_off).trailing_zeros();
+		let pot_mask = self.pot_mask >> col_off;
+		let intv_len = pot_mask.trailing_ones();
+		let intv_mask = (1u128 << intv_len) - 1;
+		let ng_mask = (self.ng_mask >> col_off) & intv_mask;
+
+		let group_len = self.groups[grp_off as usize] as u32;
+		if group_len <= intv_len {
+			let max_offset = ng_mask.trailing_zeros().min(intv_len - group_len);
+			for offset in 0..=max_offset {
+				if ng_mask >> (offset + group_len) & 1 == 1 {
+					continue;
+				}
+				let next_col = col_off + offset + group_len + 1;
+				let cnt = self.count_arrangements_from_cached(next_col, grp_off + 1);
+				if rank < cnt {
+					let start = (col_off + offset) as usize;
+					row[start .. start + group_len as usize].fill(b'#');
+					return self.unrank_from(next_col, grp_off + 1, rank, row);
+				}
+				rank -= cnt;
+			}
+		}
+		assert!(ng_mask == 0);
+		self.unrank_from(col_off + intv_len + 1, grp_off, rank, row)
+	}
+
+	fn arrangement(&mut self, rank: u64, row_len: usize) -> String {
+		let mut row = vec![b'.'; row_len];
+		self.unrank_from(0, 0, rank, &mut row);
+		String::from_utf8(row).unwrap()
+	}
+
+	fn new(ng_mask: u128, na_mask: u128, groups: &'a [u8]) -> Self {
+		Solver { ng_mask, pot_mask: ng_mask | na_mask, groups, cache: HashMap::new(), debug: false }
+	}
+
 	pub fn count_arrangements(ng_mask: u128, na_mask: u128, groups: &[u8], debug: bool) -> u64 {
 		if debug {
 			println!("count_arrangements(");
@@ -76,52 +119,275 @@ impl<'a> Solver<'a> {
 	}
 }
 
-fn main() {
-	let input = include_str!("../inputs/day12.txt");
-	let mut records = vec![];
-	for line in input.lines() {
-		let mut ng_mask = 0;
-		let mut na_mask = 0;
-		let mut row_len = None;
-		for (i, c) in line.char_indices() {
-			match c {
-				'.' => {},
-				'#' => ng_mask |= 1u128 << i,
-				'?' => na_mask |= 1u128 << i,
-				' ' => {
-					row_len = Some(i);
-					break;
-				},
-				_ => unreachable!()
+trait Count: Copy {
+	const ZERO: Self;
+	const ONE: Self;
+	fn checked_add(self, other: Self) -> Option<Self>;
+}
+macro_rules! impl_count {
+	($($t:ty),*) => { $(
+		impl Count for $t {
+			const ZERO: Self = 0;
+			const ONE: Self = 1;
+			fn checked_add(self, other: Self) -> Option<Self> { <$t>::checked_add(self, other) }
+		}
+	)* };
+}
+impl_count!(u64, u128);
+
+// Bottom-up alternative to Solver: table[col][grp] is the number of arrangements
+// of the columns from col onwards using the groups from grp onwards.
+// The table is kept between rows to avoid reallocating it.
+struct TableSolver<T: Count> {
+	table: Vec<T>,
+}
+impl<T: Count> TableSolver<T> {
+	fn new() -> Self {
+		TableSolver { table: vec![] }
+	}
+	// Returns None if the count overflows T
+	fn count_arrangements(&mut self, ng_mask: u128, na_mask: u128, groups: &[u8], row_len: usize) -> Option<T> {
+		let pot_mask = ng_mask | na_mask;
+		let stride = groups.len() + 1;
+		self.table.clear();
+		self.table.resize((row_len + 1) * stride, T::ZERO);
+		let table = &mut self.table;
+		table[row_len * stride + groups.len()] = T::ONE;
+		let mut run_len = 0; // number of potentially damaged springs from col onwards
+		for col in (0..row_len).rev() {
+			run_len = if pot_mask >> col & 1 == 1 { run_len + 1 } else { 0 };
+			for grp in 0..=groups.len() {
+				let mut cnt = T::ZERO;
+				if ng_mask >> col & 1 == 0 { // operational
+					cnt = table[(col + 1) * stride + grp];
+				}
+				if grp < groups.len() {
+					let end = col + groups[grp] as usize;
+					// damaged group from col to end, followed by an operational spring or the end of the row
+					if run_len >= groups[grp] as usize && (end == row_len || ng_mask >> end & 1 == 0) {
+						let next_col = (end + 1).min(row_len);
+						cnt = cnt.checked_add(table[next_col * stride + grp + 1])?;
+					}
+				}
+				table[col * stride + grp] = cnt;
 			}
 		}
-		let row_len = row_len.unwrap();
-		let mut groups = vec![];
-		for group_str in line[row_len+1..].split(',') {
-			groups.push(group_str.parse::<u8>().unwrap());
+		Some(table[0])
+	}
+}
+
+// Lazily yields every valid arrangement of a row, as a string of '#' and '.'
+struct Arrangements<'a> {
+	solver: Solver<'a>,
+	row_len: usize,
+	next_rank: u64,
+	count: u64,
+}
+impl<'a> Arrangements<'a> {
+	fn new(ng_mask: u128, na_mask: u128, groups: &'a [u8], row_len: usize) -> Self {
+		let mut solver = Solver::new(ng_mask, na_mask, groups);
+		let count = solver.count_arrangements_from_cached(0, 0);
+		Arrangements { solver, row_len, next_rank: 0, count }
+	}
+	// Picks an arrangement uniformly at random among all of them (not only the remaining ones)
+	fn sample(&mut self, rng: &mut Rng) -> Option<String> {
+		if self.count == 0 {
+			return None;
+		}
+		Some(self.solver.arrangement(rng.below(self.count), self.row_len))
+	}
+}
+impl Iterator for Arrangements<'_> {
+	type Item = String;
+	fn next(&mut self) -> Option<String> {
+		if self.next_rank == self.count {
+			return None;
 		}
-		records.push((ng_mask, na_mask, groups, row_len));
+		self.next_rank += 1;
+		Some(self.solver.arrangement(self.next_rank - 1, self.row_len))
 	}
+}
+
+// splitmix64, seeded from the standard library's per-process random hasher keys
+struct Rng(u64);
+impl Rng {
+	fn new() -> Self {
+		use std::hash::{BuildHasher, Hasher};
+		Rng(std::collections::hash_map::RandomState::new().build_hasher().finish())
+	}
+	fn next_u64(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^ (z >> 31)
+	}
+	// Uniform in 0..n, rejecting the values which would bias the modulo
+	fn below(&mut self, n: u64) -> u64 {
+		let zone = u64::MAX - u64::MAX % n;
+		loop {
+			let x = self.next_u64();
+			if x < zone {
+				return x % n;
+			}
+		}
+	}
+}
+
+fn matches_record(row: &str, ng_mask: u128, na_mask: u128, groups: &[u8]) -> bool {
+	let fits_masks = row.bytes().enumerate().all(|(i, c)| match c {
+		b'#' => (ng_mask | na_mask) >> i & 1 == 1,
+		_ => ng_mask >> i & 1 == 0,
+	});
+	fits_masks && row.split('.').filter(|grp| !grp.is_empty()).map(|grp| grp.len() as u8).eq(groups.iter().copied())
+}
+
+// (mask of damaged springs, mask of unknown springs, groups, row length)
+fn parse_record(line: &str) -> (u128, u128, Vec<u8>, usize) {
+	let mut ng_mask = 0;
+	let mut na_mask = 0;
+	let mut row_len = None;
+	for (i, c) in line.char_indices() {
+		match c {
+			'.' => {},
+			'#' => ng_mask |= 1u128 << i,
+			'?' => na_mask |= 1u128 << i,
+			' ' => {
+				row_len = Some(i);
+				break;
+			},
+			_ => unreachable!()
+		}
+	}
+	let row_len = row_len.unwrap();
+	let mut groups = vec![];
+	for group_str in line[row_len+1..].split(',') {
+		groups.push(group_str.parse::<u8>().unwrap());
+	}
+	(ng_mask, na_mask, groups, row_len)
+}
+
+fn unfold((ng_mask, na_mask, groups, row_len): &(u128, u128, Vec<u8>, usize)) -> (u128, u128, Vec<u8>, usize) {
+	let (ng_mask, na_mask, row_len) = (*ng_mask, *na_mask, *row_len);
+	assert!(row_len*5 + 4 <= 128);
+	let mut ng_mask_ext = ng_mask;
+	let mut na_mask_ext = na_mask;
+	let mut groups_ext = groups.clone();
+	for _ in 0..4 {
+		ng_mask_ext = ng_mask_ext << (row_len+1) | ng_mask;
+		na_mask_ext = na_mask_ext << (row_len+1) | 1u128 << row_len | na_mask;
+		groups_ext.extend_from_slice(groups);
+	}
+	(ng_mask_ext, na_mask_ext, groups_ext, row_len*5 + 4)
+}
+
+fn main() {
+	let show = std::env::args().any(|arg| arg == "--show");
+	let sample = std::env::args().any(|arg| arg == "--sample");
+	let bench = std::env::args().any(|arg| arg == "--bench");
+	let mut rng = Rng::new();
+
+	let input = include_str!("../inputs/day12.txt");
+	let records: Vec<_> = input.lines().map(parse_record).collect();
 	
+	let mut table_solver = TableSolver::<u64>::new();
 	let mut arr_cnt_sum = 0;
-	for (ng_mask, na_mask, groups, _) in records.iter() {
-		arr_cnt_sum += Solver::count_arrangements(*ng_mask, *na_mask, groups, false);
+	for (ng_mask, na_mask, groups, row_len) in records.iter() {
+		let arr_cnt = table_solver.count_arrangements(*ng_mask, *na_mask, groups, *row_len).unwrap();
+		arr_cnt_sum += arr_cnt;
+		if show {
+			for arr in Arrangements::new(*ng_mask, *na_mask, groups, *row_len) {
+				println!("{}", arr);
+			}
+		}
 	}
 	println!("part 1: {}", arr_cnt_sum);
 	
-	let mut arr_cnt_sum = 0;
-	for (ng_mask, na_mask, groups, row_len) in records {
-		assert!(row_len*5 + 4 <= 128);
-		let mut ng_mask_ext = ng_mask;
-		let mut na_mask_ext = na_mask;
-		let mut groups_ext = groups.clone();
-		for _ in 0..4 {
-			ng_mask_ext = ng_mask_ext << (row_len+1) | ng_mask;
-			na_mask_ext = na_mask_ext << (row_len+1) | 1u128 << row_len | na_mask;
-			groups_ext.extend_from_slice(&groups);
+	let unfolded: Vec<_> = records.iter().map(unfold).collect();
+
+	let mut table_solver = TableSolver::<u128>::new();
+	let mut arr_cnt_sum = 0u128;
+	for (ng_mask, na_mask, groups, row_len) in &unfolded {
+		let arr_cnt = table_solver.count_arrangements(*ng_mask, *na_mask, groups, *row_len)
+			.expect("arrangement count overflow");
+		arr_cnt_sum = arr_cnt_sum.checked_add(arr_cnt).expect("arrangement count overflow");
+		if sample {
+			let arr = Arrangements::new(*ng_mask, *na_mask, groups, *row_len).sample(&mut rng);
+			if let Some(arr) = &arr {
+				assert!(matches_record(arr, *ng_mask, *na_mask, groups));
+			}
+			println!("{}", arr.as_deref().unwrap_or("(no arrangement)"));
 		}
-		
-		arr_cnt_sum += Solver::count_arrangements(ng_mask_ext, na_mask_ext, &groups_ext, false);
 	}
 	println!("part 2: {}", arr_cnt_sum);
+
+	if bench {
+		const RUNS: u32 = 20;
+		let start = std::time::Instant::now();
+		let mut memo_sum = 0;
+		for _ in 0..RUNS {
+			for (ng_mask, na_mask, groups, _) in &unfolded {
+				memo_sum += Solver::count_arrangements(*ng_mask, *na_mask, groups, false) as u128;
+			}
+		}
+		let memo_time = start.elapsed() / RUNS;
+		let start = std::time::Instant::now();
+		let mut table_sum = 0;
+		for _ in 0..RUNS {
+			for (ng_mask, na_mask, groups, row_len) in &unfolded {
+				table_sum += table_solver.count_arrangements(*ng_mask, *na_mask, groups, *row_len).unwrap();
+			}
+		}
+		let table_time = start.elapsed() / RUNS;
+		assert_eq!(memo_sum, table_sum);
+		println!("memoized: {:?} per run", memo_time);
+		println!("table:    {:?} per run", table_time);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn example() -> Vec<(u128, u128, Vec<u8>, usize)> {
+		include_str!("../inputs/day12_ex.txt").lines().map(parse_record).collect()
+	}
+
+	#[test]
+	fn solvers_agree() {
+		let mut table_solver = TableSolver::<u128>::new();
+		for ((ng_mask, na_mask, groups, row_len), expected) in example().into_iter().zip([1, 4, 1, 1, 4, 10]) {
+			assert_eq!(table_solver.count_arrangements(ng_mask, na_mask, &groups, row_len), Some(expected));
+			assert_eq!(Solver::count_arrangements(ng_mask, na_mask, &groups, false) as u128, expected);
+		}
+		for (record, expected) in example().iter().zip([1, 16384, 1, 16, 2500, 506250]) {
+			let (ng_mask, na_mask, groups, row_len) = unfold(record);
+			assert_eq!(table_solver.count_arrangements(ng_mask, na_mask, &groups, row_len), Some(expected));
+			assert_eq!(Solver::count_arrangements(ng_mask, na_mask, &groups, false) as u128, expected);
+		}
+	}
+
+	#[test]
+	fn enumerate_arrangements() {
+		for ((ng_mask, na_mask, groups, row_len), expected) in example().into_iter().zip([1, 4, 1, 1, 4, 10]) {
+			let arrs: Vec<String> = Arrangements::new(ng_mask, na_mask, &groups, row_len).collect();
+			assert_eq!(arrs.len(), expected);
+			assert!(arrs.iter().all(|arr| matches_record(arr, ng_mask, na_mask, &groups)));
+			let mut distinct = arrs.clone();
+			distinct.sort();
+			distinct.dedup();
+			assert_eq!(distinct.len(), arrs.len());
+		}
+	}
+
+	#[test]
+	fn sample_arrangements() {
+		let mut rng = Rng::new();
+		for (ng_mask, na_mask, groups, row_len) in example() {
+			let mut arrs = Arrangements::new(ng_mask, na_mask, &groups, row_len);
+			for _ in 0..10 {
+				assert!(matches_record(&arrs.sample(&mut rng).unwrap(), ng_mask, na_mask, &groups));
+			}
+		}
+	}
 }
\ No newline at end of file
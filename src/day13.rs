@@ -1,43 +1,165 @@
 #![feature(let_chains)]
 
+// A line of the pattern, as a bitset of rocks (#) of any width
+type Line = Vec<u64>;
+
+fn line_get(line: &Line, i: usize) -> bool {
+	line[i / 64] >> (i % 64) & 1 == 1
+}
+fn line_flip(line: &mut Line, i: usize) {
+	line[i / 64] ^= 1 << (i % 64);
+}
+fn line_set(line: &mut Line, i: usize) {
+	if line.len() <= i / 64 {
+		line.resize(i / 64 + 1, 0);
+	}
+	line[i / 64] |= 1 << (i % 64);
+}
+// Positions at which two lines of the same width differ
+fn line_diffs<'a>(l1: &'a Line, l2: &'a Line) -> impl Iterator<Item = usize> + 'a {
+	l1.iter().zip(l2).enumerate().flat_map(|(word_idx, (w1, w2))| {
+		let mut diff = w1 ^ w2;
+		std::iter::from_fn(move || {
+			if diff == 0 { return None; }
+			let bit = diff.trailing_zeros() as usize;
+			diff &= diff - 1;
+			Some(word_idx * 64 + bit)
+		})
+	})
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Axis {
+	Horizontal,
+	Vertical,
+}
+
+#[derive(Clone, Debug)]
+struct Reflection {
+	axis: Axis,
+	// number of rows above, or columns left of, the mirror line
+	pos: usize,
+	// (x, y) positions of the cells which differ from their mirror image,
+	// each paired with that mirror image; flipping either cell removes the smudge
+	smudges: Vec<[(usize, usize); 2]>,
+}
+impl Reflection {
+	fn summary(&self) -> usize {
+		match self.axis {
+			Axis::Horizontal => self.pos * 100,
+			Axis::Vertical => self.pos,
+		}
+	}
+}
+
+// What to sum when a pattern has several reflection lines with the expected number of smudges
+#[derive(Clone, Copy, Debug)]
+enum SumPolicy {
+	// the first one, horizontal lines first, then top to bottom / left to right
+	First,
+	All,
+	// there must be exactly one
+	Unique,
+}
+
 #[derive(Clone, Debug)]
 struct Pattern {
 	width: usize,
-	lines: Vec<u32>,
+	lines: Vec<Line>,
 }
 impl Pattern {
 	fn empty() -> Self {
 		Pattern { width: 0, lines: vec![] }
 	}
 	fn transpose(&self) -> Pattern {
-		let mut lines = vec![0; self.width];
-		for (line_idx, mut line) in self.lines.iter().copied().enumerate() {
-			for col in 0..self.width {
-				lines[col] |= (line & 1) << line_idx;
-				line >>= 1;
+		let mut lines = vec![vec![0; self.lines.len().div_ceil(64)]; self.width];
+		for (line_idx, line) in self.lines.iter().enumerate() {
+			for (col, col_line) in lines.iter_mut().enumerate() {
+				if line_get(line, col) {
+					line_set(col_line, line_idx);
+				}
 			}
 		}
 		Pattern { width: self.lines.len(), lines }
 	}
-	fn is_hor_refl(&self, line_pos: usize, smudged: bool) -> bool {
-		let mut smudges = 0;
+	fn flip(&self, (x, y): (usize, usize)) -> Pattern {
+		let mut res = self.clone();
+		line_flip(&mut res.lines[y], x);
+		res
+	}
+	// Draws the pattern with its mirror line marked, as in the puzzle statement:
+	// column numbers for a vertical line, row numbers for a horizontal one
+	fn render(&self, refl: &Reflection) -> String {
+		let mut out = String::new();
+		let cells = |line: &Line| (0..self.width).map(|x| if line_get(line, x) { '#' } else { '.' }).collect::<String>();
+		match refl.axis {
+			Axis::Vertical => {
+				let numbers: String = (1..=self.width).map(|x| char::from_digit(x as u32 % 10, 10).unwrap()).collect();
+				let marks: String = (0..self.width)
+					.map(|x| if x + 1 == refl.pos { '>' } else if x == refl.pos { '<' } else { ' ' })
+					.collect();
+				out.push_str(&format!("{}\n{}\n", numbers, marks));
+				for line in &self.lines {
+					out.push_str(&format!("{}\n", cells(line)));
+				}
+				out.push_str(&format!("{}\n{}\n", marks, numbers));
+			},
+			Axis::Horizontal => {
+				let num_width = self.lines.len().to_string().len();
+				for (y, line) in self.lines.iter().enumerate() {
+					let mark = if y + 1 == refl.pos { 'v' } else if y == refl.pos { '^' } else { ' ' };
+					out.push_str(&format!("{:>w$}{}{}{}{}\n", y + 1, mark, cells(line), mark, y + 1, w = num_width));
+				}
+			},
+		}
+		out
+	}
+	// Returns the differing cells as (x, y) pairs, or None if there are more than max_smudges
+	fn hor_refl_smudges(&self, line_pos: usize, max_smudges: usize) -> Option<Vec<[(usize, usize); 2]>> {
+		let mut smudges = vec![];
 		for dist in 0..line_pos.min(self.lines.len()-line_pos) {
-			smudges += (self.lines[line_pos-1-dist] ^ self.lines[line_pos+dist]).count_ones();
+			let (y1, y2) = (line_pos-1-dist, line_pos+dist);
+			for x in line_diffs(&self.lines[y1], &self.lines[y2]) {
+				if smudges.len() == max_smudges {
+					return None;
+				}
+				smudges.push([(x, y1), (x, y2)]);
+			}
 		}
-		smudges == smudged as u32
+		Some(smudges)
+	}
+	fn find_hor_refls(&self, max_smudges: usize) -> impl Iterator<Item = (usize, Vec<[(usize, usize); 2]>)> + '_ {
+		(1..self.lines.len()).filter_map(move |line_pos|
+			self.hor_refl_smudges(line_pos, max_smudges).map(|smudges| (line_pos, smudges)))
 	}
-	fn find_hor_refl(&self, smudged: bool) -> Option<usize> {
-		(1..self.lines.len()).find(|line_pos| self.is_hor_refl(*line_pos, smudged))
+	// All reflection lines with at most max_smudges differences
+	fn find_reflections(&self, max_smudges: usize) -> Vec<Reflection> {
+		let mut refls: Vec<Reflection> = self.find_hor_refls(max_smudges)
+			.map(|(pos, smudges)| Reflection { axis: Axis::Horizontal, pos, smudges })
+			.collect();
+		refls.extend(self.transpose().find_hor_refls(max_smudges).map(|(pos, smudges)| Reflection {
+			axis: Axis::Vertical,
+			pos,
+			smudges: smudges.into_iter().map(|cells| cells.map(|(y, x)| (x, y))).collect(),
+		}));
+		refls
 	}
-	fn summarize_refl(&self, smudged: bool) -> usize {
-		self.find_hor_refl(smudged).map(|x| x*100).or_else(||
-			self.transpose().find_hor_refl(smudged)
-		).unwrap()
+	// Fails if there is no reflection with exactly the given number of smudges,
+	// or if there are several and the policy requires a unique one
+	fn summarize_refl(&self, smudges: usize, policy: SumPolicy) -> Result<usize, String> {
+		let refls: Vec<Reflection> = self.find_reflections(smudges).into_iter()
+			.filter(|refl| refl.smudges.len() == smudges)
+			.collect();
+		match (policy, &refls[..]) {
+			(SumPolicy::All, _) => Ok(refls.iter().map(|refl| refl.summary()).sum()),
+			(_, []) => Err(format!("no reflection with {} smudge(s)", smudges)),
+			(SumPolicy::First, [refl, ..]) | (SumPolicy::Unique, [refl]) => Ok(refl.summary()),
+			(SumPolicy::Unique, _) => Err(format!("{} reflections with {} smudge(s)", refls.len(), smudges)),
+		}
 	}
 }
 
-fn main() {
-	let input = include_str!("../inputs/day13.txt");
+fn parse_patterns(input: &str) -> Vec<Pattern> {
 	let mut patterns = vec![Pattern::empty()];
 	for line in input.lines() {
 		if line.is_empty() {
@@ -46,13 +168,136 @@ fn main() {
 			let cur_pat = patterns.last_mut().unwrap();
 			if cur_pat.width == 0 { cur_pat.width = line.len(); }
 			assert_eq!(cur_pat.width, line.len());
-			let mut mask = 0;
+			let mut mask = vec![0; line.len().div_ceil(64)];
 			for (i,c) in line.char_indices() {
-				mask |= ((c == '#') as u32) << i;
+				if c == '#' {
+					line_set(&mut mask, i);
+				}
 			}
 			cur_pat.lines.push(mask);
 		}
 	}
-	println!("part 1: {}", patterns.iter().map(|pat| pat.summarize_refl(false)).sum::<usize>());
-	println!("part 2: {}", patterns.iter().map(|pat| pat.summarize_refl(true)).sum::<usize>());
+	patterns
+}
+
+fn main() {
+	let patterns = parse_patterns(include_str!("../inputs/day13.txt"));
+	let policy = if std::env::args().any(|arg| arg == "--sum-unique") {
+		SumPolicy::Unique
+	} else if std::env::args().any(|arg| arg == "--sum-all") {
+		SumPolicy::All
+	} else {
+		SumPolicy::First
+	};
+	// patterns are numbered from 1; those which don't fit the policy are reported and skipped
+	for (part, smudges) in [(1, 0), (2, 1)] {
+		let mut sum = 0;
+		for (i, pat) in patterns.iter().enumerate() {
+			match pat.summarize_refl(smudges, policy) {
+				Ok(summary) => sum += summary,
+				Err(err) => println!("pattern {}: {}", i + 1, err),
+			}
+		}
+		println!("part {}: {}", part, sum);
+	}
+
+	if std::env::args().any(|arg| arg == "--desmudge") {
+		for (i, pat) in patterns.iter().enumerate() {
+			let refls = pat.find_reflections(1);
+			for refl in refls.iter().filter(|refl| refl.smudges.len() == 1) {
+				let smudge = refl.smudges[0][0];
+				let fixed = pat.flip(smudge);
+				// 1-based, to match the rendering
+				println!("pattern {}: smudge at row {}, column {}", i + 1, smudge.1 + 1, smudge.0 + 1);
+				print!("{}", fixed.render(refl));
+				println!();
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example() {
+		let patterns = parse_patterns(include_str!("../inputs/day13_ex.txt"));
+		for (smudges, expected) in [(0, [5, 400]), (1, [300, 100])] {
+			for policy in [SumPolicy::First, SumPolicy::All, SumPolicy::Unique] {
+				let summaries: Vec<usize> = patterns.iter().map(|pat| pat.summarize_refl(smudges, policy).unwrap()).collect();
+				assert_eq!(summaries, expected);
+			}
+		}
+	}
+
+	#[test]
+	fn render_example() {
+		let patterns = parse_patterns(include_str!("../inputs/day13_ex.txt"));
+		let rendered: Vec<String> = patterns.iter().map(|pat| pat.render(&pat.find_reflections(0)[0])).collect();
+		assert_eq!(rendered, [
+			"123456789\n    ><   \n#.##..##.\n..#.##.#.\n##......#\n##......#\n..#.##.#.\n..##..##.\n#.#.##.#.\n    ><   \n123456789\n",
+			"1 #...##..# 1\n2 #....#..# 2\n3 ..##..### 3\n4v#####.##.v4\n5^#####.##.^5\n6 ..##..### 6\n7 #....#..# 7\n",
+		]);
+	}
+
+	#[test]
+	fn smudge_positions() {
+		let patterns = parse_patterns(include_str!("../inputs/day13_ex.txt"));
+		let smudged: Vec<Vec<_>> = patterns.iter()
+			.map(|pat| pat.find_reflections(1).into_iter()
+				.filter(|refl| refl.smudges.len() == 1)
+				.map(|refl| (refl.axis, refl.pos, refl.smudges))
+				.collect())
+			.collect();
+		assert_eq!(smudged, [
+			vec![(Axis::Horizontal, 3, vec![[(0, 0), (0, 5)]])],
+			vec![(Axis::Horizontal, 1, vec![[(4, 0), (4, 1)]])],
+		]);
+	}
+
+	#[test]
+	fn several_smudges() {
+		// mirrored around the vertical line after column 2, but for two cells
+		let pat = &parse_patterns("#..#\n.##.\n#...\n.#..")[0];
+		let refls = pat.find_reflections(2);
+		let refl = refls.iter().find(|refl| refl.axis == Axis::Vertical && refl.pos == 2).unwrap();
+		// closest to the mirror line first
+		assert_eq!(refl.smudges, [[(1, 3), (2, 3)], [(0, 2), (3, 2)]]);
+		assert!(pat.find_reflections(1).iter().all(|refl| !(refl.axis == Axis::Vertical && refl.pos == 2)));
+	}
+
+	#[test]
+	fn several_lines() {
+		// every line is a reflection line of a blank pattern
+		let pat = &parse_patterns("....\n....\n....\n....")[0];
+		assert_eq!(pat.find_reflections(0).len(), 6);
+		assert_eq!(pat.summarize_refl(0, SumPolicy::First), Ok(100));
+		assert_eq!(pat.summarize_refl(0, SumPolicy::All), Ok(606));
+		assert!(pat.summarize_refl(0, SumPolicy::Unique).is_err());
+		// no reflection at all
+		assert_eq!(pat.summarize_refl(1, SumPolicy::All), Ok(0));
+		assert!(pat.summarize_refl(1, SumPolicy::First).is_err());
+		assert!(pat.summarize_refl(1, SumPolicy::Unique).is_err());
+	}
+
+	#[test]
+	fn wide_pattern() {
+		// 150 columns, mirrored around the vertical line after column 120
+		let rows: Vec<String> = (0..3).map(|y| {
+			let mut row: Vec<char> = (0..120).map(|x| if (x * x + y * 7) % 5 < 2 { '#' } else { '.' }).collect();
+			let mirror: Vec<char> = row[90..].iter().rev().copied().collect();
+			row.extend(mirror);
+			row.into_iter().collect()
+		}).collect();
+		let pat = &parse_patterns(&rows.join("\n"))[0];
+		assert_eq!(pat.width, 150);
+		let refls = pat.find_reflections(0);
+		assert!(refls.iter().any(|refl| refl.axis == Axis::Vertical && refl.pos == 120));
+
+		let smudged = pat.flip((140, 1));
+		let refls = smudged.find_reflections(1);
+		let refl = refls.iter().find(|refl| refl.axis == Axis::Vertical && refl.pos == 120).unwrap();
+		assert_eq!(refl.smudges, [[(99, 1), (140, 1)]]);
+	}
 }
\ No newline at end of file
@@ -1,91 +1,282 @@
-use std::collections::HashMap;
-
-const SIZE: usize = 100;
+use util::cycle;
 
+// One bitset per row, so up to 128 columns
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct Grid {
-	cubic_rocks: [u128; SIZE],
-	round_rocks: [u128; SIZE],
+	width: usize,
+	height: usize,
+	cubic_rocks: Vec<u128>,
+	round_rocks: Vec<u128>,
+}
+impl Grid {
+	fn parse(input: &[u8]) -> Result<Grid, String> {
+		let lines: Vec<&[u8]> = input.split(|b| *b == b'\n').filter(|line| !line.is_empty()).collect();
+		let width = lines.first().map(|line| line.len()).unwrap_or(0);
+		if width > 128 {
+			return Err(format!("grid too wide: {} columns", width));
+		}
+		let mut grid = Grid { width, height: lines.len(), cubic_rocks: vec![0; lines.len()], round_rocks: vec![0; lines.len()] };
+		for (row, line) in lines.into_iter().enumerate() {
+			if line.len() != width {
+				return Err(format!("row {} has {} columns instead of {}", row + 1, line.len(), width));
+			}
+			for (col, cell) in line.iter().enumerate() {
+				match cell {
+					b'#' => grid.cubic_rocks[row] |= 1u128 << col,
+					b'O' => grid.round_rocks[row] |= 1u128 << col,
+					b'.' => {},
+					_ => return Err(format!("unexpected {:?} at row {}, column {}", *cell as char, row + 1, col + 1)),
+				}
+			}
+		}
+		Ok(grid)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Dir {
+	N,
+	E,
+	S,
+	W,
+}
+impl Dir {
+	fn from_char(c: char) -> Result<Dir, String> {
+		match c {
+			'N' => Ok(Dir::N),
+			'E' => Ok(Dir::E),
+			'S' => Ok(Dir::S),
+			'W' => Ok(Dir::W),
+			_ => Err(format!("invalid direction: {:?}", c)),
+		}
+	}
+	fn parse(s: &str) -> Result<Dir, String> {
+		let mut chars = s.chars();
+		match (chars.next(), chars.next()) {
+			(Some(c), None) => Dir::from_char(c),
+			_ => Err(format!("expected a single direction, got {:?}", s)),
+		}
+	}
+	fn parse_seq(seq: &str) -> Result<Vec<Dir>, String> {
+		seq.chars().map(Dir::from_char).collect()
+	}
 }
 
-fn roll_north(grid: &mut Grid) {
-	let mut wall_dist = [0u8; SIZE];
-	for row in 0..SIZE {
+// Rolls each column towards the north or south edge, row by row
+fn tilt_vertical(grid: &mut Grid, north: bool) {
+	let mut wall_dist = vec![0u8; grid.width];
+	for i in 0..grid.height {
+		let row = if north { i } else { grid.height-1-i };
 		let row_cubic = grid.cubic_rocks[row];
 		let mut row_round = grid.round_rocks[row];
-		for col in 0..SIZE {
+		for (col, dist) in wall_dist.iter_mut().enumerate() {
 			if (row_cubic >> col) & 1 != 0 {
-				wall_dist[col] = 0;
+				*dist = 0;
 			} else if (row_round >> col) & 1 != 0 {
-				if wall_dist[col] > 0 {
+				if *dist > 0 {
 					row_round &= !(1u128 << col);
-					grid.round_rocks[row - wall_dist[col] as usize] |= 1u128 << col;
+					let dest = if north { row - *dist as usize } else { row + *dist as usize };
+					grid.round_rocks[dest] |= 1u128 << col;
 				}
 			} else {
-				wall_dist[col] += 1;
+				*dist += 1;
 			}
 		}
 		grid.round_rocks[row] = row_round;
 	}
 }
 
-fn calculate_load(grid: &Grid) -> u32 {
+// Packs the round rocks of each segment between cubic rocks towards the west or east end
+fn tilt_horizontal(grid: &mut Grid, west: bool) {
+	for (row_cubic, row_round) in grid.cubic_rocks.iter().zip(grid.round_rocks.iter_mut()) {
+		let mut new_round = 0;
+		let mut start = 0;
+		while start < grid.width {
+			let end = (start + (row_cubic >> start).trailing_zeros() as usize).min(grid.width);
+			let seg_mask = ((1u128 << (end - start)) - 1) << start;
+			let cnt = (*row_round & seg_mask).count_ones() as usize;
+			let packed = (1u128 << cnt) - 1;
+			new_round |= if west { packed << start } else { packed << (end - cnt) };
+			start = end + 1;
+		}
+		*row_round = new_round;
+	}
+}
+
+fn tilt(grid: &mut Grid, dir: Dir) {
+	match dir {
+		Dir::N => tilt_vertical(grid, true),
+		Dir::S => tilt_vertical(grid, false),
+		Dir::W => tilt_horizontal(grid, true),
+		Dir::E => tilt_horizontal(grid, false),
+	}
+}
+
+// Each round rock weighs as much as its distance to the opposite edge, plus one
+fn calculate_load(grid: &Grid, side: Dir) -> u32 {
 	let mut load = 0;
-	for row in 0..SIZE {
-		load += grid.round_rocks[row].count_ones() * (SIZE - row) as u32;
+	for (row, row_round) in grid.round_rocks.iter().enumerate() {
+		load += match side {
+			Dir::N => row_round.count_ones() * (grid.height - row) as u32,
+			Dir::S => row_round.count_ones() * (row + 1) as u32,
+			Dir::W | Dir::E => (0..grid.width).filter(|col| (row_round >> col) & 1 != 0)
+				.map(|col| if side == Dir::W { grid.width - col } else { col + 1 } as u32)
+				.sum(),
+		};
 	}
 	load
 }
 
-fn rotate_masks(grid: &[u128; SIZE]) -> [u128; SIZE] {
-	let mut new_grid = [0u128; SIZE];
-	for row in 0..SIZE {
-		let mut row_mask = grid[row];
-		for col in 0..SIZE {
-			new_grid[col] |= (row_mask & 1) << (SIZE-1-row);
-			row_mask >>= 1;
-		}
+fn spin_cycle(grid: &mut Grid, seq: &[Dir]) {
+	for dir in seq {
+		tilt(grid, *dir);
 	}
-	new_grid
 }
-fn spin_cycle(grid: &mut Grid) {
-	for _ in 0..4 {
-		roll_north(grid);
-		grid.cubic_rocks = rotate_masks(&grid.cubic_rocks);
-		grid.round_rocks = rotate_masks(&grid.round_rocks);
-	}
+
+fn arg_value(name: &str) -> Option<String> {
+	std::env::args().skip_while(|arg| arg != name).nth(1)
 }
 
 fn main() {
 	let input = include_bytes!("../inputs/day14.txt");
-	let mut grid = Grid {
-		cubic_rocks: [0; SIZE],
-		round_rocks: [0; SIZE],
+	let mut grid = match Grid::parse(input) {
+		Ok(grid) => grid,
+		Err(err) => {
+			println!("{}", err);
+			return;
+		}
+	};
+	
+	// --seq and --side run a variant of part 2 with another tilt sequence or load measurement
+	let variant = (arg_value("--seq"), arg_value("--side"));
+	let variant = match variant {
+		(None, None) => None,
+		(seq, side) => {
+			let seq = Dir::parse_seq(seq.as_deref().unwrap_or("NWSE"));
+			let side = side.as_deref().map(Dir::parse).unwrap_or(Ok(Dir::N));
+			match (seq, side) {
+				(Ok(seq), Ok(side)) => Some((seq, side)),
+				(Err(err), _) | (_, Err(err)) => {
+					println!("{}", err);
+					return;
+				}
+			}
+		}
+	};
+	let initial = grid.clone();
+
+	tilt(&mut grid, Dir::N);
+	println!("part 1: {}", calculate_load(&grid, Dir::N));
+	
+	let spin_seq = |seq: &[Dir]| {
+		let seq = seq.to_vec();
+		move |grid: &Grid| {
+			let mut grid = grid.clone();
+			spin_cycle(&mut grid, &seq);
+			grid
+		}
 	};
-	for (row, line) in input.split(|b| *b == b'\n').enumerate() {
-		assert!(line.len() == SIZE);
-		for (col, cell) in line.iter().enumerate() {
-			if *cell == b'#' {
-				grid.cubic_rocks[row] |= 1u128 << col;
+	let spin = spin_seq(&[Dir::N, Dir::W, Dir::S, Dir::E]);
+	let cycle = cycle::find_cycle_brent(grid.clone(), &spin);
+	let grid = cycle::state_at(grid, &spin, 1_000_000_000, cycle);
+	println!("part 2: {}", calculate_load(&grid, Dir::N));
+
+	if let Some((seq, side)) = variant {
+		let spin = spin_seq(&seq);
+		let cycle = cycle::find_cycle_brent(initial.clone(), &spin);
+		let grid = cycle::state_at(initial, &spin, 1_000_000_000, cycle);
+		println!("variant: {}", calculate_load(&grid, side));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn example() -> Grid {
+		Grid::parse(include_bytes!("../inputs/day14_ex.txt")).unwrap()
+	}
+
+	fn render(grid: &Grid) -> String {
+		let mut out = String::new();
+		for row in 0..grid.height {
+			for col in 0..grid.width {
+				out.push(if grid.cubic_rocks[row] >> col & 1 != 0 { '#' } else if grid.round_rocks[row] >> col & 1 != 0 { 'O' } else { '.' });
+			}
+			out.push('\n');
+		}
+		out
+	}
+
+	// Moves rocks one tile at a time until none can move
+	fn naive_tilt(rows: &mut [Vec<u8>], dir: Dir) {
+		let (dx, dy) = match dir { Dir::N => (0, -1), Dir::E => (1, 0), Dir::S => (0, 1), Dir::W => (-1, 0) };
+		let (w, h) = (rows[0].len() as i32, rows.len() as i32);
+		let mut moved = true;
+		while moved {
+			moved = false;
+			for y in 0..h {
+				for x in 0..w {
+					let (nx, ny) = (x + dx, y + dy);
+					if rows[y as usize][x as usize] == b'O' && nx >= 0 && nx < w && ny >= 0 && ny < h && rows[ny as usize][nx as usize] == b'.' {
+						rows[y as usize][x as usize] = b'.';
+						rows[ny as usize][nx as usize] = b'O';
+						moved = true;
+					}
+				}
 			}
-			if *cell == b'O' {
-				grid.round_rocks[row] |= 1u128 << col;
+		}
+	}
+
+	#[test]
+	fn example_loads() {
+		let mut grid = example();
+		tilt(&mut grid, Dir::N);
+		assert_eq!(calculate_load(&grid, Dir::N), 136);
+
+		let mut grid = example();
+		spin_cycle(&mut grid, &[Dir::N, Dir::W, Dir::S, Dir::E]);
+		assert_eq!(render(&grid), ".....#....\n....#...O#\n...OO##...\n.OO#......\n.....OOO#.\n.O#...O#.#\n....O#....\n......OOOO\n#...O###..\n#..OO#....\n");
+
+		let spin = |grid: &Grid| {
+			let mut grid = grid.clone();
+			spin_cycle(&mut grid, &[Dir::N, Dir::W, Dir::S, Dir::E]);
+			grid
+		};
+		let cycle = cycle::find_cycle_brent(example(), spin);
+		assert_eq!(calculate_load(&cycle::state_at(example(), spin, 1_000_000_000, cycle), Dir::N), 64);
+	}
+
+	#[test]
+	fn tilt_and_load_any_side() {
+		for dir in [Dir::N, Dir::E, Dir::S, Dir::W] {
+			let mut grid = example();
+			tilt(&mut grid, dir);
+			let mut rows: Vec<Vec<u8>> = render(&example()).lines().map(|line| line.bytes().collect()).collect();
+			naive_tilt(&mut rows, dir);
+			assert_eq!(render(&grid).lines().map(|line| line.as_bytes()).collect::<Vec<_>>(), rows, "tilt {:?}", dir);
+
+			for side in [Dir::N, Dir::E, Dir::S, Dir::W] {
+				let mut load = 0;
+				for (y, row) in rows.iter().enumerate() {
+					for (x, cell) in row.iter().enumerate() {
+						if *cell == b'O' {
+							load += match side { Dir::N => 10 - y, Dir::S => y + 1, Dir::W => 10 - x, Dir::E => x + 1 } as u32;
+						}
+					}
+				}
+				assert_eq!(calculate_load(&grid, side), load, "tilt {:?}, load on {:?}", dir, side);
 			}
 		}
 	}
-	
-	roll_north(&mut grid);
-	println!("part 1: {}", calculate_load(&grid));
-	
-	let mut history = HashMap::<[u128; SIZE], usize>::new();
-	let mut max_step = 1_000_000_000;
-	let mut step = 0;
-	while step < max_step {
-		if let Some(prev_step) = history.insert(grid.round_rocks, step) {
-			// cycle detected!
-			max_step = step + (1_000_000_000 - prev_step) % (step - prev_step);
+
+	#[test]
+	fn parse_dirs() {
+		assert_eq!(Dir::parse("E"), Ok(Dir::E));
+		for s in ["", "NE", "X", "n"] {
+			assert!(Dir::parse(s).is_err(), "{:?}", s);
 		}
-		spin_cycle(&mut grid);
-		step += 1;
+		assert_eq!(Dir::parse_seq("NNEE"), Ok(vec![Dir::N, Dir::N, Dir::E, Dir::E]));
+		assert!(Dir::parse_seq("NWSX").is_err());
 	}
-	println!("part 2: {}", calculate_load(&grid));
 }
\ No newline at end of file
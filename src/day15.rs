@@ -9,40 +9,177 @@ fn hash(s: &[u8]) -> u8 {
 }
 
 #[derive(Clone, Debug)]
-struct Pair<'a> {
+struct Lens<'a> {
 	label: &'a [u8],
-	focal: i8,
+	focal: u32,
+}
+
+// The puzzle's HASHMAP: 256 boxes indexed by the hash of the label,
+// each keeping its lenses in insertion order
+struct LensMap<'a> {
+	boxes: Vec<Vec<Lens<'a>>>,
+}
+impl<'a> LensMap<'a> {
+	fn new() -> Self {
+		LensMap { boxes: vec![vec![]; 256] }
+	}
+	// Replaces the lens with the same label in place, or adds it at the back of its box.
+	// Returns the previous focal length.
+	fn insert(&mut self, label: &'a [u8], focal: u32) -> Option<u32> {
+		let box_ref = &mut self.boxes[hash(label) as usize];
+		if let Some(lens) = box_ref.iter_mut().find(|lens| lens.label == label) {
+			Some(std::mem::replace(&mut lens.focal, focal))
+		} else {
+			box_ref.push(Lens { label, focal });
+			None
+		}
+	}
+	fn remove(&mut self, label: &[u8]) -> Option<u32> {
+		let box_ref = &mut self.boxes[hash(label) as usize];
+		let pos = box_ref.iter().position(|lens| lens.label == label)?;
+		Some(box_ref.remove(pos).focal)
+	}
+	fn get(&self, label: &[u8]) -> Option<u32> {
+		self.boxes[hash(label) as usize].iter().find(|lens| lens.label == label).map(|lens| lens.focal)
+	}
+	// (box, slot, label, focal length) for every lens, in box then slot order
+	fn iter(&self) -> impl Iterator<Item = (usize, usize, &'a [u8], u32)> + '_ {
+		self.boxes.iter().enumerate().flat_map(|(box_idx, lenses)|
+			lenses.iter().enumerate().map(move |(slot_idx, lens)| (box_idx, slot_idx, lens.label, lens.focal)))
+	}
+	fn focusing_power(&self) -> usize {
+		self.iter().map(|(box_idx, slot_idx, _, focal)| (1 + box_idx) * (1 + slot_idx) * focal as usize).sum()
+	}
+	fn apply(&mut self, step: &Step<'a>) {
+		match *step {
+			Step::Insert(label, focal) => { self.insert(label, focal); },
+			Step::Remove(label) => { self.remove(label); },
+		}
+	}
+}
+
+// Non-empty boxes, as in the puzzle statement, eg. "Box 3: [ot 7] [ab 5] [pc 6]"
+impl std::fmt::Display for LensMap<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (box_idx, lenses) in self.boxes.iter().enumerate() {
+			if lenses.is_empty() { continue; }
+			write!(f, "Box {}:", box_idx)?;
+			for lens in lenses {
+				write!(f, " [{} {}]", String::from_utf8_lossy(lens.label), lens.focal)?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+enum Step<'a> {
+	Insert(&'a [u8], u32),
+	Remove(&'a [u8]),
+}
+impl<'a> Step<'a> {
+	// Steps are either "label=N" or "label-", where label is made of letters
+	fn parse(step: &'a [u8]) -> Result<Step<'a>, String> {
+		let err = || format!("invalid step: {:?}", String::from_utf8_lossy(step));
+		let (label, res) = if let Some((label, focal)) = step.split_once(|c| *c == b'=') {
+			let focal = std::str::from_utf8(focal).ok()
+				.filter(|focal| focal.bytes().all(|c| c.is_ascii_digit()))
+				.and_then(|focal| focal.parse().ok())
+				.ok_or_else(err)?;
+			(label, Step::Insert(label, focal))
+		} else if let Some(label) = step.strip_suffix(b"-") {
+			(label, Step::Remove(label))
+		} else {
+			return Err(err());
+		};
+		if label.is_empty() || !label.iter().all(|c| c.is_ascii_alphabetic()) {
+			return Err(err());
+		}
+		Ok(res)
+	}
+}
+
+// Newlines are ignored, as per the puzzle statement, wherever they are.
+// Any other byte which can't appear in a step is rejected.
+fn normalize(input: &[u8]) -> Result<Vec<u8>, String> {
+	let mut res = Vec::with_capacity(input.len());
+	for (i, c) in input.iter().copied().enumerate() {
+		match c {
+			b'\n' | b'\r' => {},
+			b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'=' | b'-' | b',' => res.push(c),
+			_ => return Err(format!("unexpected byte {:?} at offset {}", c as char, i)),
+		}
+	}
+	Ok(res)
 }
 
 fn main() {
-	let input = include_bytes!("../inputs/day15.txt");
-	
+	let trace = std::env::args().any(|arg| arg == "--trace");
+	let input = if std::env::args().any(|arg| arg == "--example") {
+		&include_bytes!("../inputs/day15_ex.txt")[..]
+	} else {
+		&include_bytes!("../inputs/day15.txt")[..]
+	};
+	let input = match normalize(input) {
+		Ok(input) => input,
+		Err(err) => {
+			println!("{}", err);
+			return;
+		}
+	};
+
 	println!("part 1: {}", input.split(|c| *c == b',').map(|step| hash(step) as u32).sum::<u32>());
-	
-	let mut boxes: [Vec<Pair>; 256] = vec![vec![]; 256].try_into().unwrap();
+
+	let mut lenses = LensMap::new();
 	for step in input.split(|c| *c == b',') {
-		let (label, focal) = if step[step.len()-1] == b'-' {
-			(&step[0..step.len()-1], -1)
-		} else {
-			assert!(step[step.len()-2] == b'=');
-			(&step[0..step.len()-2], (step[step.len()-1] - b'0') as i8)
-		};
-		let box_ref = &mut boxes[hash(label) as usize];
-		let label_pos = box_ref.iter().position(|p| p.label == label);
-		if focal != -1 {
-			if let Some(i) = label_pos {
-				box_ref[i].focal = focal;
-			} else {
-				box_ref.push(Pair { label, focal });
+		match Step::parse(step) {
+			Ok(parsed) => {
+				lenses.apply(&parsed);
+				if trace {
+					println!("After {:?}:", String::from_utf8_lossy(step));
+					println!("{}", lenses);
+				}
+			},
+			Err(err) => {
+				println!("{}", err);
+				return;
 			}
-		} else if let Some(i) = label_pos {
-			box_ref.remove(i);
 		}
 	}
-	let power = boxes.into_iter().enumerate().map(|(box_idx, pairs)|
-		(1 + box_idx) * pairs.into_iter().enumerate().map(|(slot_idx, pair)|
-			(1 + slot_idx) * (pair.focal as usize)
-		).sum::<usize>()
-	).sum::<usize>();
-	println!("part 2: {}", power);
+	println!("part 2: {}", lenses.focusing_power());
+
+	if let Some(label) = std::env::args().skip_while(|arg| arg != "--lens").nth(1) {
+		match lenses.get(label.as_bytes()) {
+			Some(focal) => println!("{}: focal length {} in box {}", label, focal, hash(label.as_bytes())),
+			None => println!("{}: not in any box", label),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example() {
+		let example = normalize(include_bytes!("../inputs/day15_ex.txt")).unwrap();
+		assert_eq!(example.split(|c| *c == b',').map(|step| hash(step) as u32).sum::<u32>(), 1320);
+		let mut lenses = LensMap::new();
+		for step in example.split(|c| *c == b',') {
+			lenses.apply(&Step::parse(step).unwrap());
+		}
+		assert_eq!((lenses.get(b"rn"), lenses.get(b"cm"), lenses.get(b"ot"), lenses.get(b"qp")), (Some(1), Some(2), Some(7), None));
+		assert!(lenses.iter().map(|(box_idx, slot_idx, label, _)| (box_idx, slot_idx, label))
+			.eq([(0, 0, &b"rn"[..]), (0, 1, b"cm"), (3, 0, b"ot"), (3, 1, b"ab"), (3, 2, b"pc")]));
+		assert_eq!(lenses.focusing_power(), 145);
+	}
+
+	#[test]
+	fn invalid_steps() {
+		for step in ["", "=1", "ab", "ab=", "ab=x", "ab=-1", "a1-", "ab-1"] {
+			assert!(Step::parse(step.as_bytes()).is_err(), "{:?}", step);
+		}
+		assert!(normalize(b"ab=1,\ncd-\r\n").is_ok());
+		assert!(normalize(b"ab=1, cd-").is_err());
+	}
 }
\ No newline at end of file
@@ -1,32 +1,100 @@
+use std::collections::VecDeque;
+
 use util::Grid;
 
-#[derive(Clone)]
-enum Tile {
-	Empty,
-	MirrorSlash,
-	MirrorBackslash,
-	SplitterHor,
-	SplitterVer,
+// Beam directions, in the order of their BeamSet bits: up, left, right, down
+const DIRS: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+const DIR_NAMES: [char; 4] = ['U', 'L', 'R', 'D'];
+
+// An optical element, as the directions in which a beam leaves it,
+// for each direction in which a beam can enter it
+#[derive(Clone, Copy)]
+struct Element {
+	outputs: [u8; 4],
+}
+impl Element {
+	// Outputs for beams moving up, left, right and down, separated by '/',
+	// each given as a (possibly empty) set of the letters U, L, R and D.
+	// eg. "R/D/U/L" is a '/' mirror, and "///" absorbs every beam
+	fn parse(spec: &str) -> Result<Element, String> {
+		let sets: Vec<&str> = spec.split('/').collect();
+		if sets.len() != 4 {
+			return Err(format!("expected 4 output sets in {:?}", spec));
+		}
+		let mut outputs = [0; 4];
+		for (outs, set) in outputs.iter_mut().zip(sets) {
+			for c in set.chars() {
+				let dir = DIR_NAMES.iter().position(|name| *name == c)
+					.ok_or_else(|| format!("unknown direction {:?} in {:?}", c, spec))?;
+				*outs |= 1 << dir;
+			}
+		}
+		Ok(Element { outputs })
+	}
+	// Custom element definitions, as C:SPEC
+	fn parse_def(def: &str) -> Result<(u8, Element), String> {
+		let (c, spec) = def.split_once(':').filter(|(c, _)| c.len() == 1)
+			.ok_or_else(|| format!("element definition should be C:SPEC, got {:?}", def))?;
+		Ok((c.as_bytes()[0], Element::parse(spec)?))
+	}
 }
 
+// Optical elements by their character in the input
+struct Optics {
+	elements: [Option<Element>; 256],
+}
+impl Optics {
+	fn standard() -> Self {
+		let mut optics = Optics { elements: [None; 256] };
+		for (c, spec) in [
+			(b'.', "U/L/R/D"),
+			(b'/', "R/D/U/L"),
+			(b'\\', "L/U/D/R"),
+			(b'-', "LR/L/R/LR"),
+			(b'|', "U/UD/UD/D"),
+		] {
+			optics.register(c, Element::parse(spec).unwrap());
+		}
+		optics
+	}
+	fn register(&mut self, c: u8, element: Element) {
+		self.elements[c as usize] = Some(element);
+	}
+	fn load_grid(&self, input: &[u8]) -> Result<Grid<Element>, String> {
+		let mut unknown = None;
+		let grid = Grid::load_from_bytes(input, |c, x, y| {
+			self.elements[c as usize].unwrap_or_else(|| {
+				unknown.get_or_insert(format!("unknown element {:?} at ({}, {})", c as char, x, y));
+				Element { outputs: [0; 4] }
+			})
+		});
+		match unknown {
+			Some(err) => Err(err),
+			None => Ok(grid),
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
 struct Beam {
-	x: i8, y: i8,
-	dx: i8, dy: i8,
+	x: i32, y: i32,
+	dx: i32, dy: i32,
 }
 
-#[derive(Clone)]
+// Directions of the beams which went through a tile
+#[derive(Clone, Copy)]
 struct BeamSet {
 	mask: u8,
 }
 impl BeamSet {
 	const EMPTY: BeamSet = BeamSet { mask: 0 };
-	fn dir_idx(dx: i8, dy: i8) -> i8 {
+	fn dir_idx(dx: i32, dy: i32) -> i32 {
 		(dx + (dy+1)*3) / 2
 	}
-	fn has_dir(&self, dx: i8, dy: i8) -> bool {
+	fn has_dir(&self, dx: i32, dy: i32) -> bool {
 		self.mask & (1 << BeamSet::dir_idx(dx,dy)) != 0
 	}
-	fn add_dir(&mut self, dx: i8, dy: i8) {
+	fn add_dir(&mut self, dx: i32, dy: i32) {
 		self.mask |= 1 << BeamSet::dir_idx(dx,dy);
 	}
 	fn is_energized(&self) -> bool {
@@ -34,76 +102,281 @@ impl BeamSet {
 	}
 }
 
-fn count_energized(w: i32, h: i32, tiles: &Grid::<Tile>, beam: Beam) -> u32 {
-	let mut beams = Grid::<BeamSet>::new(w, h, BeamSet::EMPTY);
+fn propagate(tiles: &Grid<Element>, beam: Beam) -> Grid<BeamSet> {
+	let mut beams = Grid::<BeamSet>::new(tiles.width, tiles.height, BeamSet::EMPTY);
 	let mut beam_fronts = vec![beam];
-	while !beam_fronts.is_empty() {
-		let mut i = 0;
-		while i < beam_fronts.len() {
-			let beam = &mut beam_fronts[i];
-			if beam.x < 0 || beam.y < 0 || beam.x >= w as i8 || beam.y >= h as i8 {
-				beam_fronts.remove(i);
-				continue;
-			}
-			let pos = (beam.x as i32, beam.y as i32);
-			if beams[pos].has_dir(beam.dx, beam.dy) {
-				beam_fronts.remove(i);
-				continue;
+	while let Some(beam) = beam_fronts.pop() {
+		let pos = (beam.x, beam.y);
+		if !tiles.in_bounds(pos) || beams[pos].has_dir(beam.dx, beam.dy) {
+			continue;
+		}
+		beams[pos].add_dir(beam.dx, beam.dy);
+		let outputs = tiles[pos].outputs[BeamSet::dir_idx(beam.dx, beam.dy) as usize];
+		for (dir, (dx, dy)) in DIRS.into_iter().enumerate() {
+			if outputs & (1 << dir) != 0 {
+				beam_fronts.push(Beam { x: beam.x + dx, y: beam.y + dy, dx, dy });
 			}
-			beams[pos].add_dir(beam.dx, beam.dy);
-			let mut new_beam = None;
-			match tiles[pos] {
-				Tile::MirrorSlash => (beam.dx, beam.dy) = (-beam.dy, -beam.dx),
-				Tile::MirrorBackslash => (beam.dx, beam.dy) = (beam.dy, beam.dx),
-				Tile::SplitterHor if beam.dy != 0 => {
-					new_beam = Some(Beam { x: beam.x + 1, y: beam.y, dx: 1, dy: 0 });
-					(beam.dx, beam.dy) = (-1, 0);
-				},
-				Tile::SplitterVer if beam.dx != 0 => {
-					new_beam = Some(Beam { x: beam.x, y: beam.y + 1, dx: 0, dy: 1 });
-					(beam.dx, beam.dy) = (0, -1);
-				},
-				_ => {},
+		}
+	}
+	beams
+}
+
+fn count_energized(tiles: &Grid<Element>, beam: Beam) -> usize {
+	propagate(tiles, beam).data.iter().filter(|set| set.is_energized()).count()
+}
+
+// Best energized count over the beams entering from any edge tile
+fn max_energized(tiles: &Grid<Element>) -> usize {
+	let (mx, my) = (tiles.width-1, tiles.height-1);
+	let beams =
+		       (0..=mx).map(|x| Beam { x,    y:0,  dx:0,  dy:1  })
+		.chain((0..=mx).map(|x| Beam { x,    y:my, dx:0,  dy:-1 }))
+		.chain((0..=my).map(|y| Beam { x:0,  y,    dx:1,  dy:0  }))
+		.chain((0..=my).map(|y| Beam { x:mx, y,    dx:-1, dy:0  }));
+	beams.map(|beam| count_energized(tiles, beam)).max().unwrap()
+}
+
+struct BeamStats {
+	energized: usize,
+	// whether some beams go around a closed cycle
+	has_loop: bool,
+	// elements which split a beam that actually reached them
+	activated_splitters: Vec<(i32, i32)>,
+	// number of tiles traversed by the beam which lasts the longest,
+	// if all beams advance in lockstep and stop on tiles already traversed in their direction
+	longest_path: usize,
+}
+
+// Analyzes the beams through their states (a tile and a direction they entered it in):
+// each state visited in the BeamSet grid leads to the states its element outputs to
+fn analyze(tiles: &Grid<Element>, beam: Beam) -> BeamStats {
+	let beams = propagate(tiles, beam);
+	let state_idx = |(x, y): (i32, i32), dir: usize| ((x + y * tiles.width) * 4) as usize + dir;
+	let successors = |pos: (i32, i32), dir: usize| {
+		let outputs = tiles[pos].outputs[dir];
+		DIRS.into_iter().enumerate().filter_map(move |(dir2, (dx, dy))| {
+			let pos2 = (pos.0 + dx, pos.1 + dy);
+			(outputs & (1 << dir2) != 0 && tiles.in_bounds(pos2)).then_some((pos2, dir2))
+		})
+	};
+	let mut states = vec![];
+	for y in 0..tiles.height {
+		for x in 0..tiles.width {
+			for dir in 0..4 {
+				if beams[(x, y)].mask & (1 << dir) != 0 {
+					states.push(((x, y), dir));
+				}
 			}
-			beam.x += beam.dx;
-			beam.y += beam.dy;
-			if let Some(new_beam) = new_beam {
-				beam_fronts.push(new_beam);
+		}
+	}
+
+	let energized = beams.data.iter().filter(|set| set.is_energized()).count();
+	let mut activated_splitters: Vec<(i32, i32)> = states.iter()
+		.filter(|(pos, dir)| tiles[*pos].outputs[*dir].count_ones() >= 2)
+		.map(|(pos, _)| *pos)
+		.collect();
+	activated_splitters.dedup();
+
+	// Kahn's algorithm: the states which can't be peeled off in topological order are on a cycle
+	let mut in_degree = vec![0u8; (tiles.width * tiles.height * 4) as usize];
+	for (pos, dir) in &states {
+		for (pos2, dir2) in successors(*pos, *dir) {
+			in_degree[state_idx(pos2, dir2)] += 1;
+		}
+	}
+	let mut queue: Vec<_> = states.iter().copied().filter(|(pos, dir)| in_degree[state_idx(*pos, *dir)] == 0).collect();
+	let mut peeled = 0;
+	while let Some((pos, dir)) = queue.pop() {
+		peeled += 1;
+		for (pos2, dir2) in successors(pos, dir) {
+			let deg = &mut in_degree[state_idx(pos2, dir2)];
+			*deg -= 1;
+			if *deg == 0 {
+				queue.push((pos2, dir2));
 			}
-			i += 1;
 		}
 	}
-	
-	let mut energized = 0;
-	for y in 0..h {
-		for x in 0..w {
-			if beams[(x, y)].is_energized() {
-				energized += 1;
+	let has_loop = peeled < states.len();
+
+	// breadth-first search from the starting state gives the step at which each state is first reached
+	let start_dir = BeamSet::dir_idx(beam.dx, beam.dy) as usize;
+	let mut dist = vec![usize::MAX; in_degree.len()];
+	let mut longest_path = 0;
+	if tiles.in_bounds((beam.x, beam.y)) {
+		let mut queue = VecDeque::from([((beam.x, beam.y), start_dir)]);
+		dist[state_idx((beam.x, beam.y), start_dir)] = 1;
+		while let Some((pos, dir)) = queue.pop_front() {
+			let d = dist[state_idx(pos, dir)];
+			longest_path = longest_path.max(d);
+			for (pos2, dir2) in successors(pos, dir) {
+				if dist[state_idx(pos2, dir2)] == usize::MAX {
+					dist[state_idx(pos2, dir2)] = d + 1;
+					queue.push_back((pos2, dir2));
+				}
 			}
 		}
 	}
-	energized
+
+	BeamStats { energized, has_loop, activated_splitters, longest_path }
+}
+
+// Draws the beams over empty tiles as in the puzzle statement:
+// an arrow if a single beam went through, or the number of beams
+fn render(input: &[u8], beams: &Grid<BeamSet>) -> String {
+	let chars = Grid::load_from_bytes(input, |c, _, _| c);
+	let mut out = String::new();
+	for y in 0..beams.height {
+		for x in 0..beams.width {
+			let set = beams[(x, y)];
+			out.push(match chars[(x, y)] {
+				b'.' => match set.mask.count_ones() {
+					0 => '.',
+					1 => ['^', '<', '>', 'v'][set.mask.trailing_zeros() as usize],
+					n => char::from_digit(n, 10).unwrap(),
+				},
+				c => c as char,
+			});
+		}
+		out.push('\n');
+	}
+	out
 }
 
 fn main() {
 	let input = include_bytes!("../inputs/day16.txt");
-	let tiles = Grid::load_from_bytes(input, |c,_,_| match c {
-		b'.' => Tile::Empty,
-		b'/' => Tile::MirrorSlash,
-		b'\\' => Tile::MirrorBackslash,
-		b'-' => Tile::SplitterHor,
-		b'|' => Tile::SplitterVer,
-		_ => unreachable!(),
-	});
-	let (w, h) = (tiles.width, tiles.height);
-	
-	println!("part 1: {}", count_energized(w, h, &tiles, Beam { x: 0, y: 0, dx: 1, dy: 0 }));
+	// extra elements can be given as --element C:SPEC, with SPEC as in Element::parse
+	let mut optics = Optics::standard();
+	let mut args = std::env::args();
+	while let Some(arg) = args.next() {
+		if arg == "--element" {
+			let def = args.next().unwrap_or_default();
+			match Element::parse_def(&def) {
+				Ok((c, element)) => optics.register(c, element),
+				Err(err) => {
+					println!("{}", err);
+					return;
+				}
+			}
+		}
+	}
+	let tiles = match optics.load_grid(input) {
+		Ok(tiles) => tiles,
+		Err(err) => {
+			println!("{}", err);
+			return;
+		}
+	};
+	let start = Beam { x: 0, y: 0, dx: 1, dy: 0 };
+	println!("part 1: {}", count_energized(&tiles, start));
+	if std::env::args().any(|arg| arg == "--render") {
+		print!("{}", render(input, &propagate(&tiles, start)));
+	}
+	if std::env::args().any(|arg| arg == "--stats") {
+		let stats = analyze(&tiles, start);
+		println!("energized: {}", stats.energized);
+		println!("beam loop: {}", if stats.has_loop { "yes" } else { "no" });
+		println!("activated splitters: {}", stats.activated_splitters.len());
+		for (x, y) in &stats.activated_splitters {
+			println!("  ({}, {})", x, y);
+		}
+		println!("longest path: {}", stats.longest_path);
+	}
 	
-	let (mx, my) = ((w-1) as i8, (h-1) as i8);
-	let beams =
-		       (0..=mx).map(|x| Beam { x,    y:0,  dx:0,  dy:1  })
-		.chain((0..=mx).map(|x| Beam { x,    y:my, dx:0,  dy:-1 }))
-		.chain((0..=my).map(|y| Beam { x:0,  y,    dx:1,  dy:0  }))
-		.chain((0..=my).map(|y| Beam { x:mx, y,    dx:-1, dy:0  }));
-	println!("part 2: {}", beams.map(|beam| count_energized(w, h, &tiles, beam)).max().unwrap());
+	println!("part 2: {}", max_energized(&tiles));
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const START: Beam = Beam { x: 0, y: 0, dx: 1, dy: 0 };
+
+	#[test]
+	fn example() {
+		let tiles = Optics::standard().load_grid(include_bytes!("../inputs/day16_ex.txt")).unwrap();
+		assert_eq!(count_energized(&tiles, START), 46);
+		assert_eq!(max_energized(&tiles), 51);
+	}
+
+	#[test]
+	fn example_stats() {
+		let tiles = Optics::standard().load_grid(include_bytes!("../inputs/day16_ex.txt")).unwrap();
+		let stats = analyze(&tiles, START);
+		assert_eq!(stats.energized, 46);
+		assert!(stats.has_loop);
+		assert_eq!(stats.activated_splitters.len(), 7);
+	}
+
+	#[test]
+	fn straight_stats() {
+		// a split beam whose longer half crosses 4 tiles after the splitter
+		let tiles = Optics::standard().load_grid(b"..|\n...\n...\n...\n...\n").unwrap();
+		let stats = analyze(&tiles, START);
+		assert_eq!(stats.energized, 7);
+		assert!(!stats.has_loop);
+		assert_eq!(stats.activated_splitters, [(2, 0)]);
+		assert_eq!(stats.longest_path, 7);
+		// a beam going around a loop of mirrors
+		let tiles = Optics::standard().load_grid(b".-.\\\n.\\./\n").unwrap();
+		assert!(analyze(&tiles, START).has_loop);
+	}
+
+	fn custom_grid(input: &str, defs: &[&str]) -> Grid<Element> {
+		let mut optics = Optics::standard();
+		for def in defs {
+			let (c, element) = Element::parse_def(def).unwrap();
+			optics.register(c, element);
+		}
+		optics.load_grid(input.as_bytes()).unwrap()
+	}
+
+	#[test]
+	fn absorber() {
+		// beams stop on the absorber, whichever side they come from
+		let tiles = custom_grid(".X...\n..X..\n", &["X:///"]);
+		assert_eq!(count_energized(&tiles, START), 2);
+		assert_eq!(count_energized(&tiles, Beam { x: 4, y: 0, dx: -1, dy: 0 }), 4);
+		assert_eq!(count_energized(&tiles, Beam { x: 2, y: 0, dx: 0, dy: 1 }), 2);
+	}
+
+	#[test]
+	fn three_way_splitter() {
+		// beams moving right leave up, right and down
+		let tiles = custom_grid("...\n.T.\n...\n", &["T:U/L/URD/D"]);
+		let beams = propagate(&tiles, Beam { x: 0, y: 1, dx: 1, dy: 0 });
+		assert_eq!(beams.data.iter().filter(|set| set.is_energized()).count(), 5);
+		assert!(beams[(1, 0)].has_dir(0, -1) && beams[(2, 1)].has_dir(1, 0) && beams[(1, 2)].has_dir(0, 1));
+		// other beams go through
+		assert_eq!(count_energized(&tiles, Beam { x: 1, y: 0, dx: 0, dy: 1 }), 3);
+	}
+
+	#[test]
+	fn one_way_mirror() {
+		// reflects beams moving right upwards, like '/', and lets beams moving left through
+		let tiles = custom_grid("...\n.M.\n", &["M:U/L/U/D"]);
+		let beams = propagate(&tiles, Beam { x: 0, y: 1, dx: 1, dy: 0 });
+		assert!(beams[(1, 0)].has_dir(0, -1) && !beams[(2, 1)].is_energized());
+		let beams = propagate(&tiles, Beam { x: 2, y: 1, dx: -1, dy: 0 });
+		assert!(beams[(0, 1)].has_dir(-1, 0) && !beams[(1, 0)].is_energized());
+	}
+
+	#[test]
+	fn invalid_definitions() {
+		for def in ["", "X", "XY:///", "X:U/L/R", "X:U/L/R/Q", ":///"] {
+			assert!(Element::parse_def(def).is_err(), "{:?}", def);
+		}
+	}
+
+	#[test]
+	fn wide_grid() {
+		// the beam crosses 150 tiles before being reflected down on the last row of a 200-wide grid
+		let input = format!("{}\n{}\\{}\n", ".".repeat(200), ".".repeat(150), ".".repeat(49));
+		let tiles = Optics::standard().load_grid(input.as_bytes()).unwrap();
+		assert_eq!((tiles.width, tiles.height), (200, 2));
+		assert_eq!(count_energized(&tiles, START), 200);
+		assert_eq!(count_energized(&tiles, Beam { x: 0, y: 1, dx: 1, dy: 0 }), 151);
+		assert_eq!(count_energized(&tiles, Beam { x: 199, y: 1, dx: -1, dy: 0 }), 49 + 1 + 1);
+		let beams = propagate(&tiles, Beam { x: 199, y: 1, dx: -1, dy: 0 });
+		assert!(beams[(150, 0)].has_dir(0, -1) && beams[(199, 1)].has_dir(-1, 0));
+	}
+}
\ No newline at end of file
@@ -1,6 +1,5 @@
-use std::{collections::HashMap, iter::Peekable};
-
 use chumsky::prelude::*;
+use util::Grid;
 
 struct Step {
 	dx: i32, dy: i32,
@@ -22,93 +21,294 @@ fn parse_step() -> impl Parser<char, Step, Error=Simple<char>> {
 		})
 }
 
-struct MergeIter<T: Ord, I1: Iterator<Item=T>, I2: Iterator<Item=T>> {
-	left: Peekable<I1>,
-	right: Peekable<I2>,
+fn parse_plan(input: &str) -> Result<Vec<Step>, String> {
+	input.lines()
+		.map(|line| parse_step().parse(line).map_err(|err| format!("{}\n{:?}", line, err)))
+		.collect()
 }
-impl<T: Ord, I1: Iterator<Item=T>, I2: Iterator<Item=T>> MergeIter<T,I1,I2> {
-	fn new(left: I1, right: I2) -> Self {
-		MergeIter { left: left.peekable(), right: right.peekable() }
+
+// Corners of the trench, in digging order, starting from the origin
+fn trace_polygon(plan: &[Step]) -> Vec<(i64, i64)> {
+	let mut vertices = vec![(0, 0)];
+	let (mut x, mut y) = (0, 0);
+	for step in plan {
+		x += (step.dx * step.steps) as i64;
+		y += (step.dy * step.steps) as i64;
+		vertices.push((x, y));
 	}
+	vertices
 }
-impl<T: Ord, I1: Iterator<Item=T>, I2: Iterator<Item=T>> Iterator for MergeIter<T,I1,I2> {
-	type Item = (bool, T);
-	fn next(&mut self) -> Option<Self::Item> {
-		if let Some(lhs) = self.left.peek() {
-			if self.right.peek().map(|rhs| rhs < lhs).unwrap_or(false) {
-				Some((true, self.right.next().unwrap()))
-			} else {
-				Some((false, self.left.next().unwrap()))
-			}
-		} else {
-			self.right.next().map(|rhs| (true, rhs))
+
+// Step indices are 0-based, but displayed as 1-based line numbers
+#[derive(Debug, PartialEq, Eq)]
+enum PlanError {
+	ZeroLength { step: usize },
+	NotClosed { offset: (i64, i64) },
+	// two trenches meet at a single point, other than where consecutive steps join
+	Crossing { steps: (usize, usize), at: (i64, i64) },
+	// two trenches run along each other
+	Overlap { steps: (usize, usize), from: (i64, i64), to: (i64, i64) },
+}
+impl std::fmt::Display for PlanError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PlanError::ZeroLength { step } =>
+				write!(f, "line {}: zero-length step", step + 1),
+			PlanError::NotClosed { offset } =>
+				write!(f, "plan does not return to its start: ends at offset {:?}", offset),
+			PlanError::Crossing { steps, at } =>
+				write!(f, "lines {} and {}: trenches cross at {:?}", steps.0 + 1, steps.1 + 1, at),
+			PlanError::Overlap { steps, from, to } =>
+				write!(f, "lines {} and {}: trenches overlap from {:?} to {:?}", steps.0 + 1, steps.1 + 1, from, to),
 		}
 	}
 }
 
-fn main() {
-	let input = include_str!("../inputs/day18.txt");
-	let mut plan = vec![];
-	for line in input.lines() {
-		match parse_step().parse(line) {
-			Ok(step) => plan.push(step),
-			Err(err) => {
-				println!("{}", line);
-				println!("{:?}", err);
-				return;
+// A trench, by its step index and its bounding box
+struct Segment {
+	step: usize,
+	min: (i64, i64),
+	max: (i64, i64),
+}
+
+// Checks that the plan digs a simple closed loop, which the volume computation relies on
+fn validate_plan(plan: &[Step], vertices: &[(i64, i64)]) -> Vec<PlanError> {
+	let mut errors = vec![];
+	for (step, s) in plan.iter().enumerate() {
+		if s.steps == 0 {
+			errors.push(PlanError::ZeroLength { step });
+		}
+	}
+	let end = *vertices.last().unwrap();
+	let closed = end == (0, 0);
+	if !closed {
+		errors.push(PlanError::NotClosed { offset: end });
+	}
+
+	// Trenches are axis-aligned, so the intersection of two of them is the
+	// intersection of their bounding boxes
+	let segments: Vec<Segment> = (0..plan.len())
+		.filter(|i| plan[*i].steps != 0)
+		.map(|step| {
+			let (a, b) = (vertices[step], vertices[step+1]);
+			Segment { step, min: (a.0.min(b.0), a.1.min(b.1)), max: (a.0.max(b.0), a.1.max(b.1)) }
+		})
+		.collect();
+	for (j, seg2) in segments.iter().enumerate() {
+		for (i, seg1) in segments[..j].iter().enumerate() {
+			let from = (seg1.min.0.max(seg2.min.0), seg1.min.1.max(seg2.min.1));
+			let to = (seg1.max.0.min(seg2.max.0), seg1.max.1.min(seg2.max.1));
+			if from.0 > to.0 || from.1 > to.1 {
+				continue;
+			}
+			let steps = (seg1.step, seg2.step);
+			if from != to {
+				errors.push(PlanError::Overlap { steps, from, to });
+				continue;
+			}
+			// consecutive steps (ignoring zero-length ones) share the vertex where they join
+			let joint = if j == i + 1 {
+				Some(vertices[seg2.step])
+			} else if closed && i == 0 && j == segments.len() - 1 {
+				Some(vertices[seg1.step])
+			} else {
+				None
+			};
+			if joint != Some(from) {
+				errors.push(PlanError::Crossing { steps, at: from });
 			}
 		}
 	}
-	
-	let mut rows = HashMap::new();
-	let mut x = 0;
-	let mut y = 0;
+	errors
+}
+
+// Cubic meters dug out, trench included: shoelace formula for the area of the
+// polygon through the centers of the trench cells, then Pick's theorem
+fn lagoon_volume(vertices: &[(i64, i64)]) -> i64 {
+	let mut area2 = 0;
+	let mut boundary = 0;
+	for w in vertices.windows(2) {
+		let ((x1, y1), (x2, y2)) = (w[0], w[1]);
+		area2 += x1 * y2 - x2 * y1;
+		boundary += (x2 - x1).abs() + (y2 - y1).abs();
+	}
+	// A = I + B/2 - 1, and we want I + B
+	(area2.abs() + boundary) / 2 + 1
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Cell {
+	Outside,
+	Trench(u32),
+	Interior,
+}
+
+// Rasterizes the lagoon, with a margin of one cell around it
+fn rasterize(plan: &[Step], vertices: &[(i64, i64)]) -> Grid<Cell> {
+	let min_x = vertices.iter().map(|v| v.0).min().unwrap();
+	let max_x = vertices.iter().map(|v| v.0).max().unwrap();
+	let min_y = vertices.iter().map(|v| v.1).min().unwrap();
+	let max_y = vertices.iter().map(|v| v.1).max().unwrap();
+	let (w, h) = ((max_x - min_x + 3) as i32, (max_y - min_y + 3) as i32);
+	let mut grid = Grid::new(w, h, Cell::Outside);
+
+	let (mut x, mut y) = ((1 - min_x) as i32, (1 - min_y) as i32);
 	for step in plan {
-		if step.dy == 0 {
-			x += step.dx * step.steps;
-		} else {
-			for _ in 0..step.steps {
-				rows.entry(y.min(y + step.dy)).or_insert_with(|| vec![]).push(x);
-				y += step.dy;
-			}
+		for _ in 0..step.steps {
+			x += step.dx;
+			y += step.dy;
+			grid[(x, y)] = Cell::Trench(step.color);
 		}
 	}
-	assert!(x == 0 && y == 0);
-	
-	for row in rows.values_mut() {
-		row.sort();
-	}
-	let min_y = *rows.keys().min().unwrap();
-	let max_y = *rows.keys().max().unwrap() + 1;
-	rows.insert(min_y-1, vec![]);
-	rows.insert(max_y, vec![]);
-	
-	let mut area = 0;
-	for y in min_y..=max_y {
-		let top_row = rows.get(&(y-1)).unwrap();
-		let bot_row = rows.get(&y).unwrap();
-		let mut top_in = false;
-		let mut bot_in = false;
-		let mut run = None;
-		println!("top: {:?}", top_row);
-		println!("bot: {:?}", bot_row);
-		for (side, x) in MergeIter::new(top_row.iter(), bot_row.iter()) {
-			if side {
-				bot_in = !bot_in;
-			} else {
-				top_in = !top_in;
+
+	// everything which can't be reached from the margin is inside
+	let mut outside = Grid::new(w, h, false);
+	let mut stack = vec![(0, 0)];
+	outside[(0, 0)] = true;
+	while let Some((x, y)) = stack.pop() {
+		for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+			let neigh = (x + dx, y + dy);
+			if grid.get(neigh) == Some(&Cell::Outside) && !outside[neigh] {
+				outside[neigh] = true;
+				stack.push(neigh);
 			}
-			let row_in = bot_in || top_in;
-			if run.is_some() && !row_in {
-				let x2 = run.take().unwrap();
-				println!("{}: {} {}", y, x2, x);
-				area += x - x2 + 1;
-			} else if run.is_none() && row_in {
-				run = Some(x);
+		}
+	}
+	for (cell, out) in grid.data.iter_mut().zip(outside.data.iter()) {
+		if *cell == Cell::Outside && !out {
+			*cell = Cell::Interior;
+		}
+	}
+	grid
+}
+
+// Diagrams as in the puzzle statement: the trench only, or with the interior dug out
+fn render_text(grid: &Grid<Cell>, with_interior: bool) -> String {
+	let mut out = String::new();
+	for y in 1..grid.height-1 {
+		for x in 1..grid.width-1 {
+			out.push(match grid[(x, y)] {
+				Cell::Trench(_) => '#',
+				Cell::Interior if with_interior => '#',
+				_ => '.',
+			});
+		}
+		out.push('\n');
+	}
+	out
+}
+
+// Binary PPM: trench cells in their color, the interior in gray, the rest in black
+fn write_ppm(grid: &Grid<Cell>, path: &str) -> std::io::Result<()> {
+	let mut data = format!("P6\n{} {}\n255\n", grid.width, grid.height).into_bytes();
+	for cell in grid.data.iter() {
+		let rgb = match cell {
+			Cell::Outside => 0x000000,
+			Cell::Trench(color) => *color,
+			Cell::Interior => 0x808080,
+		};
+		data.extend_from_slice(&rgb.to_be_bytes()[1..]);
+	}
+	std::fs::write(path, data)
+}
+
+fn main() {
+	let input = if std::env::args().any(|arg| arg == "--example") {
+		include_str!("../inputs/day18_ex.txt")
+	} else {
+		include_str!("../inputs/day18.txt")
+	};
+	let plan = match parse_plan(input) {
+		Ok(plan) => plan,
+		Err(err) => {
+			println!("{}", err);
+			return;
+		}
+	};
+
+	let vertices = trace_polygon(&plan);
+	let errors = validate_plan(&plan, &vertices);
+	if !errors.is_empty() {
+		for err in errors {
+			println!("{}", err);
+		}
+		return;
+	}
+	println!("part 1: {}", lagoon_volume(&vertices));
+
+	let text = std::env::args().any(|arg| arg == "--text");
+	let image = std::env::args().skip_while(|arg| arg != "--image").nth(1);
+	if text || image.is_some() {
+		let grid = rasterize(&plan, &vertices);
+		if text {
+			println!("{}", render_text(&grid, false));
+			print!("{}", render_text(&grid, true));
+		}
+		if let Some(path) = image {
+			if let Err(err) = write_ppm(&grid, &path) {
+				println!("{}: {}", path, err);
 			}
 		}
-		println!();
-		assert!(run.is_none());
 	}
-	println!("part 1: {}", area);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example() {
+		let plan = parse_plan(include_str!("../inputs/day18_ex.txt")).unwrap();
+		let vertices = trace_polygon(&plan);
+		assert!(validate_plan(&plan, &vertices).is_empty());
+		assert_eq!(lagoon_volume(&vertices), 62);
+		let grid = rasterize(&plan, &vertices);
+		assert_eq!(grid.data.iter().filter(|cell| **cell != Cell::Outside).count(), 62);
+		assert_eq!(render_text(&grid, false),
+			"#######\n#.....#\n###...#\n..#...#\n..#...#\n###.###\n#...#..\n##..###\n.#....#\n.######\n");
+		assert_eq!(render_text(&grid, true),
+			"#######\n#######\n#######\n..#####\n..#####\n#######\n#####..\n#######\n.######\n.######\n");
+	}
+
+	fn plan_errors(dirs: &str) -> Vec<PlanError> {
+		let input: Vec<String> = dirs.split(", ").map(|step| format!("{} (#000000)", step)).collect();
+		let plan = parse_plan(&input.join("\n")).unwrap();
+		validate_plan(&plan, &trace_polygon(&plan))
+	}
+
+	#[test]
+	fn valid_plans() {
+		assert_eq!(plan_errors("R 2, D 2, L 2, U 2"), []);
+		let plan = parse_plan(include_str!("../inputs/day18_ex.txt")).unwrap();
+		assert_eq!(validate_plan(&plan, &trace_polygon(&plan)), []);
+	}
+
+	#[test]
+	fn zero_length() {
+		assert_eq!(plan_errors("R 2, D 0, D 2, L 2, U 2"), [PlanError::ZeroLength { step: 1 }]);
+	}
+
+	#[test]
+	fn not_closed() {
+		assert_eq!(plan_errors("R 2, D 2, L 1"), [PlanError::NotClosed { offset: (1, 2) }]);
+	}
+
+	#[test]
+	fn crossing() {
+		// the fourth step cuts through the first one
+		let errors = plan_errors("R 2, D 2, L 1, U 3, L 1, D 1");
+		assert_eq!(errors, [PlanError::Crossing { steps: (0, 3), at: (1, 0) }]);
+		assert_eq!(errors[0].to_string(), "lines 1 and 4: trenches cross at (1, 0)");
+	}
+
+	#[test]
+	fn overlap() {
+		// the last step digs back along the first one, after the fourth one touched it
+		let errors = plan_errors("R 2, D 1, L 1, U 1, L 1");
+		assert_eq!(errors, [
+			PlanError::Crossing { steps: (0, 3), at: (1, 0) },
+			PlanError::Overlap { steps: (0, 4), from: (0, 0), to: (1, 0) },
+		]);
+		assert_eq!(errors[1].to_string(), "lines 1 and 5: trenches overlap from (0, 0) to (1, 0)");
+	}
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use chumsky::prelude::*;
 
@@ -32,54 +32,173 @@ fn parse_node() -> impl Parser<char, Node, Error=Simple<char>> {
 	).map(|((ty, id), out)| Node { id, ty, out })
 }
 
-fn main() {
-	let input = include_str!("../inputs/day20.txt");
+fn parse_network(input: &str) -> HashMap<String, Node> {
 	let mut nodes = HashMap::new();
 	for line in input.lines() {
 		let node = parse_node().parse(line).unwrap();
 		nodes.insert(node.id.clone(), node);
 	}
-	nodes.insert("rx".to_owned(), Node { id: "rx".to_owned(), ty: NodeType::Output, out: vec![] });
-	
-	// Check that input has the expected structure:
-	
+	// Modules which are only ever pulsed, like rx, are outputs
+	let mut outputs: Vec<String> = nodes.values().flat_map(|n| n.out.iter()).filter(|id| !nodes.contains_key(*id)).cloned().collect();
+	outputs.sort();
+	outputs.dedup();
+	for id in outputs {
+		nodes.insert(id.clone(), Node { id, ty: NodeType::Output, out: vec![] });
+	}
+	nodes
+}
+
+// Pulse-by-pulse simulation of the network
+struct Simulator<'a> {
+	nodes: &'a HashMap<String, Node>,
+	ff_states: HashMap<&'a str, bool>,
+	// last pulse received by each NAND from each of its inputs
+	nand_inputs: HashMap<&'a str, HashMap<&'a str, bool>>,
+}
+impl<'a> Simulator<'a> {
+	fn new(nodes: &'a HashMap<String, Node>) -> Self {
+		let mut ff_states = HashMap::new();
+		let mut nand_inputs: HashMap<&str, HashMap<&str, bool>> = HashMap::new();
+		for node in nodes.values() {
+			match node.ty {
+				NodeType::Flipflop => { ff_states.insert(node.id.as_str(), false); },
+				NodeType::Nand => { nand_inputs.entry(&node.id).or_default(); },
+				_ => {},
+			}
+			for dest in &node.out {
+				if nodes.get(dest).map(|n| n.ty == NodeType::Nand).unwrap_or(false) {
+					nand_inputs.entry(dest).or_default().insert(&node.id, false);
+				}
+			}
+		}
+		Simulator { nodes, ff_states, nand_inputs }
+	}
+	// Pushes the button once, calling on_pulse(sender, high, receiver) for each pulse in order.
+	// Returns the number of low and high pulses sent.
+	fn press(&mut self, mut on_pulse: impl FnMut(&str, bool, &str)) -> [u32; 2] {
+		let mut counts = [0, 0];
+		let mut queue = VecDeque::from([("button", false, "broadcaster")]);
+		while let Some((sender, high, receiver)) = queue.pop_front() {
+			on_pulse(sender, high, receiver);
+			counts[high as usize] += 1;
+			let Some(node) = self.nodes.get(receiver) else { continue };
+			let out = match node.ty {
+				NodeType::Input => high,
+				NodeType::Flipflop => {
+					if high { continue; }
+					let state = self.ff_states.get_mut(receiver).unwrap();
+					*state = !*state;
+					*state
+				},
+				NodeType::Nand => {
+					let inputs = self.nand_inputs.get_mut(receiver).unwrap();
+					*inputs.get_mut(sender).unwrap() = high;
+					!inputs.values().all(|high| *high)
+				},
+				NodeType::Output => continue,
+			};
+			for dest in &node.out {
+				queue.push_back((&node.id, out, dest));
+			}
+		}
+		counts
+	}
+}
+
+// Total low and high pulses sent over the given number of presses
+fn simulate(nodes: &HashMap<String, Node>, presses: u32) -> [u32; 2] {
+	let mut sim = Simulator::new(nodes);
+	let mut pulses = [0, 0];
+	for _ in 0..presses {
+		let counts = sim.press(|_, _, _| {});
+		pulses[0] += counts[0];
+		pulses[1] += counts[1];
+	}
+	pulses
+}
+
+// A binary counter detected in the network
+struct Counter {
+	modulus: u16,
+	// flip-flops, from lowest to highest bit, then the counter's NAND and its inverter
+	nodes: Vec<String>,
+}
+
+fn to_dot(nodes: &HashMap<String, Node>, counters: &[Counter]) -> String {
+	let mut out = String::from("digraph network {\n");
+	for (i, ctr) in counters.iter().enumerate() {
+		out += &format!("\tsubgraph cluster_{} {{\n", i);
+		out += &format!("\t\tlabel=\"counter mod {}\";\n", ctr.modulus);
+		for id in &ctr.nodes {
+			out += &format!("\t\t\"{}\";\n", id);
+		}
+		out += "\t}\n";
+	}
+	let mut ids: Vec<&String> = nodes.keys().collect();
+	ids.sort();
+	for id in &ids {
+		let style = match nodes[*id].ty {
+			NodeType::Input => "shape=doublecircle",
+			NodeType::Nand => "shape=invhouse",
+			NodeType::Flipflop => "shape=box",
+			NodeType::Output => "shape=doubleoctagon, style=filled",
+		};
+		out += &format!("\t\"{}\" [{}];\n", id, style);
+	}
+	for id in &ids {
+		for dest in &nodes[*id].out {
+			out += &format!("\t\"{}\" -> \"{}\";\n", id, dest);
+		}
+	}
+	out += "}\n";
+	out
+}
+
+macro_rules! ensure {
+	($cond:expr) => { if !$cond { return None; } };
+}
+
+// Checks that the network has the expected structure, and returns the counters it is made of
+fn find_counters(nodes: &HashMap<String, Node>) -> Option<Vec<Counter>> {
 	// The output is driven by a single NAND gate
 	let final_nand_id = {
 		let final_nodes: Vec<&Node> = nodes.values()
 			.filter(|n| n.out.contains(&"rx".to_owned())).collect();
-		assert!(final_nodes.len() == 1);
+		ensure!(final_nodes.len() == 1);
 		let final_node = final_nodes[0];
-		assert!(final_node.ty == NodeType::Nand);
-		assert!(final_node.out.len() == 1);
+		ensure!(final_node.ty == NodeType::Nand);
+		ensure!(final_node.out.len() == 1);
 		final_node.id.clone()
 	};
 	
 	// Broadcaster is connected to multiple binary counters (flip-flop chains)
-	let mut ctr_moduli = vec![];
-	for mut ff_id in &nodes.get("broadcaster").unwrap().out {
-		let mut ff = nodes.get(ff_id).unwrap();
+	let mut counters = vec![];
+	for mut ff_id in &nodes.get("broadcaster")?.out {
+		let mut ff = nodes.get(ff_id)?;
 		// The first flip flop always outputs to a NAND
 		let nand_id = {
 			let nands: Vec<String> = ff.out.iter()
-				.filter(|id| nodes.get(*id).unwrap().ty == NodeType::Nand)
+				.filter(|id| nodes[*id].ty == NodeType::Nand)
 				.cloned()
 				.collect();
-			assert!(nands.len() == 1);
+			ensure!(nands.len() == 1);
 			nands.into_iter().next().unwrap()
 		};
-		let nand = nodes.get(&nand_id).unwrap();
+		let nand = &nodes[&nand_id];
 		// The chain is made of 12 flip-flops forming a binary counter
 		let mut ctr_modulus: u16 = 0;
 		let mut nand_outputs = vec![];
+		let mut ctr_nodes = vec![];
 		for i in 0..12 {
-			assert!(ff.ty == NodeType::Flipflop);
+			ctr_nodes.push(ff_id.clone());
+			ensure!(ff.ty == NodeType::Flipflop);
 			// The flip-flops may output to the NAND, or the NAND may output to it
 			let to_nand = ff.out.iter().any(|id| *id == nand_id);
 			let from_nand = nand.out.iter().any(|id| id == ff_id);
 			if i == 0 {
-				assert!(from_nand && to_nand); // The first flip-flop has both links
+				ensure!(from_nand && to_nand); // The first flip-flop has both links
 			} else {
-				assert!(from_nand ^ to_nand); // The others have exactly one
+				ensure!(from_nand ^ to_nand); // The others have exactly one
 			}
 			if to_nand {
 				ctr_modulus |= 1 << i; // Store a 1 bit
@@ -88,25 +207,61 @@ fn main() {
 				nand_outputs.push(ff_id);
 			}
 			if i == 11 { // Last flip-flop
-				assert!(ff.out.len() == if to_nand { 1 } else { 0 }); // Nothing after it
+				ensure!(ff.out.len() == if to_nand { 1 } else { 0 }); // Nothing after it
 			} else {
-				assert!(ff.out.len() == if to_nand { 2 } else { 1 }); // Another flip-flop after it
-				ff_id = ff.out.iter().find(|id| **id != nand_id).unwrap();
-				ff = nodes.get(ff_id).unwrap();
+				ensure!(ff.out.len() == if to_nand { 2 } else { 1 }); // Another flip-flop after it
+				ff_id = ff.out.iter().find(|id| **id != nand_id)?;
+				ff = &nodes[ff_id];
 			}
 		}
 		// Besides the ones already checked, the NAND has one more output
-		assert!(nand.out.len() == 1 + nand_outputs.len());
-		let nand2_id = nand.out.iter().find(|id| !nand_outputs.contains(id)).unwrap();
+		ensure!(nand.out.len() == 1 + nand_outputs.len());
+		let nand2_id = nand.out.iter().find(|id| !nand_outputs.contains(id))?;
 		// That output is another NAND, acting as an inverter, connected to the final NAND
-		let nand2 = nodes.get(nand2_id).unwrap();
-		assert!(nand2.ty == NodeType::Nand);
-		assert!(nand2.out.len() == 1);
-		assert!(nand2.out[0] == final_nand_id);
+		let nand2 = &nodes[nand2_id];
+		ensure!(nand2.ty == NodeType::Nand);
+		ensure!(nand2.out.len() == 1);
+		ensure!(nand2.out[0] == final_nand_id);
 		
 		// The bit pattern we extracted is the period/modulus of this binary counter
-		ctr_moduli.push(ctr_modulus);
+		ctr_nodes.extend([nand_id.clone(), nand2_id.clone()]);
+		counters.push(Counter { modulus: ctr_modulus, nodes: ctr_nodes });
+	}
+	Some(counters)
+}
+
+fn main() {
+	let input = match std::env::args().skip_while(|arg| arg != "--example").nth(1).as_deref() {
+		Some("1") => include_str!("../inputs/day20_ex1.txt"),
+		Some("2") => include_str!("../inputs/day20_ex2.txt"),
+		Some(_) => panic!("examples are numbered 1 and 2"),
+		None => include_str!("../inputs/day20.txt"),
+	};
+	let nodes = parse_network(input);
+
+	if std::env::args().any(|arg| arg == "--dot") {
+		print!("{}", to_dot(&nodes, &find_counters(&nodes).unwrap_or_default()));
+		return;
 	}
+
+	// Trace the first presses in the puzzle's format
+	let trace_presses: Option<u32> = std::env::args().skip_while(|arg| arg != "--trace").nth(1)
+		.map(|n| n.parse().expect("invalid number of presses"));
+	if let Some(trace_presses) = trace_presses {
+		let mut sim = Simulator::new(&nodes);
+		for press in 1..=trace_presses {
+			let counts = sim.press(|sender, high, receiver|
+				println!("{} -{}-> {}", sender, if high { "high" } else { "low" }, receiver));
+			println!("press {}: {} low, {} high", press, counts[0], counts[1]);
+			println!();
+		}
+	}
+
+	let Some(counters) = find_counters(&nodes) else {
+		println!("network is not made of binary counters feeding rx");
+		return;
+	};
+	let ctr_moduli: Vec<u16> = counters.iter().map(|ctr| ctr.modulus).collect();
 	
 	// Based on this structure, we can calculate the number of pulses generated
 	// for each button press.
@@ -148,9 +303,50 @@ fn main() {
 		}
 	}
 	
+	// Check the analysis against a simulation
+	assert_eq!(simulate(&nodes, 1000), pulses);
+
 	println!("part 1: {}", pulses[0] * pulses[1]);
 	
 	// We assume the periods of the counters are coprime.
 	// The output pulses low when all counters pulse hi in one step, so:
 	println!("part 2: {}", ctr_moduli.iter().map(|n| *n as u64).product::<u64>());
 }
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example_pulse_counts() {
+		let nodes = parse_network(include_str!("../inputs/day20_ex1.txt"));
+		assert_eq!(Simulator::new(&nodes).press(|_, _, _| {}), [8, 4]);
+		assert_eq!(simulate(&nodes, 1000), [8000, 4000]);
+		let nodes = parse_network(include_str!("../inputs/day20_ex2.txt"));
+		assert_eq!(simulate(&nodes, 1000), [4250, 2750]);
+	}
+
+	// the statement's trace of the second example
+	#[test]
+	fn example_trace() {
+		let nodes = parse_network(include_str!("../inputs/day20_ex2.txt"));
+		let mut sim = Simulator::new(&nodes);
+		let mut presses = vec![];
+		for _ in 0..4 {
+			let mut trace = vec![];
+			sim.press(|sender, high, receiver| trace.push(format!("{} -{}-> {}", sender, if high { "high" } else { "low" }, receiver)));
+			presses.push(trace);
+		}
+		assert_eq!(presses, [
+			&["button -low-> broadcaster", "broadcaster -low-> a", "a -high-> inv", "a -high-> con",
+				"inv -low-> b", "con -high-> output", "b -high-> con", "con -low-> output"][..],
+			&["button -low-> broadcaster", "broadcaster -low-> a", "a -low-> inv", "a -low-> con",
+				"inv -high-> b", "con -high-> output"],
+			&["button -low-> broadcaster", "broadcaster -low-> a", "a -high-> inv", "a -high-> con",
+				"inv -low-> b", "con -low-> output", "b -low-> con", "con -high-> output"],
+			&["button -low-> broadcaster", "broadcaster -low-> a", "a -low-> inv", "a -low-> con",
+				"inv -high-> b", "con -high-> output"],
+		]);
+	}
+}
\ No newline at end of file
@@ -23,6 +23,8 @@ fn intersect(r1: Ran, r2: Ran) -> Option<(Option<Ran>, Ran, Option<Ran>)> {
 }
 
 struct Map {
+	src_cat: String,
+	dst_cat: String,
 	ranges: Vec<(u32, u32, u32)>,
 }
 impl Map {
@@ -34,6 +36,18 @@ impl Map {
 		}
 		x
 	}
+	// Every value which maps to x: those from the ranges whose destination covers x,
+	// and x itself if no range covers it as a source
+	fn unmap(&self, x: u32) -> Vec<u32> {
+		let mut res: Vec<u32> = self.ranges.iter()
+			.filter(|(dst, _, len)| x >= *dst && x <= dst + (len-1))
+			.map(|(dst, src, _)| src + (x - dst))
+			.collect();
+		if !self.ranges.iter().any(|(_, src, len)| x >= *src && x <= src + (len-1)) {
+			res.push(x);
+		}
+		res
+	}
 	fn map_range(&self, ran: Ran) -> Vec<Ran> {
 		let mut dst_ranges = vec![];
 		let mut src_ranges = vec![ran];
@@ -61,34 +75,118 @@ impl Map {
 	}
 }
 
-fn input_parser() -> impl Parser<char, (Vec<u32>, Vec<Map>), Error=Simple<char>> {
+struct Almanac {
+	seeds: Vec<u32>,
+	maps: Vec<Map>,
+}
+impl Almanac {
+	// Maps to apply to go from one category to another, following
+	// the source -> destination links of the maps
+	fn chain(&self, from: &str, to: &str) -> Option<Vec<&Map>> {
+		let mut chain = vec![];
+		let mut cur = from;
+		while cur != to {
+			let map = self.maps.iter().find(|m| m.src_cat == cur)?;
+			if chain.len() == self.maps.len() {
+				return None; // cycle
+			}
+			chain.push(map);
+			cur = &map.dst_cat;
+		}
+		Some(chain)
+	}
+	// Converts a value between any two categories, in either direction
+	// (eg. location -> seed), in increasing order. Going forward there is exactly
+	// one result, going backward there can be any number of them.
+	// Returns None if the categories are not linked.
+	fn convert(&self, from: &str, to: &str, x: u32) -> Option<Vec<u32>> {
+		if let Some(chain) = self.chain(from, to) {
+			Some(vec![chain.into_iter().fold(x, |x, map| map.map(x))])
+		} else {
+			let mut xs = vec![x];
+			for map in self.chain(to, from)?.into_iter().rev() {
+				xs = xs.into_iter().flat_map(|x| map.unmap(x)).collect();
+			}
+			xs.sort_unstable();
+			xs.dedup();
+			Some(xs)
+		}
+	}
+}
+
+fn input_parser() -> impl Parser<char, Almanac, Error=Simple<char>> {
 	just("seeds: ").ignore_then(text::int(10).from_str().unwrapped().separated_by(just(" ")))
 		.then_ignore(just("\n\n"))
 		.then((
-			text::ident().then(just("-to-")).then(text::ident()).then(just(" map:\n")).ignore_then(
+			text::ident().then_ignore(just("-to-")).then(text::ident()).then_ignore(just(" map:\n")).then(
 				text::int(10).from_str().unwrapped().separated_by(just(" ")).exactly(3).map(|v| (v[0], v[1], v[2]))
-					.separated_by(just("\n")).map(|ranges| Map { ranges })
-			)
+					.separated_by(just("\n"))
+			).map(|((src_cat, dst_cat), ranges)| Map { src_cat, dst_cat, ranges })
 		).separated_by(just("\n\n")))
+		.map(|(seeds, maps)| Almanac { seeds, maps })
 }
 
 fn main() {
 	let input = include_str!("../inputs/day5.txt");
-	let (seeds, maps) = input_parser().parse(input).unwrap();
+	let almanac = input_parser().parse(input).unwrap();
 
-	let locations: Vec<u32> = seeds.iter().copied().map(|mut s| {
-		for map in &maps {
-			s = map.map(s);
-		}
-		s
-	}).collect();
+	let locations: Vec<u32> = almanac.seeds.iter().copied()
+		.map(|s| almanac.convert("seed", "location", s).unwrap()[0]).collect();
 	println!("part 1: {}", locations.into_iter().min().unwrap());
 
-	let seed_ranges: Vec<Ran> = seeds.chunks_exact(2)
+	let seed_ranges: Vec<Ran> = almanac.seeds.chunks_exact(2)
 		.map(|s| RangeInclusive::new(s[0], s[0]+(s[1]-1))).collect();
 	let mut location_ranges: Vec<Ran> = seed_ranges;
-	for map in &maps {
+	for map in almanac.chain("seed", "location").unwrap() {
 		location_ranges = location_ranges.into_iter().flat_map(|ran| map.map_range(ran)).collect();
 	}
 	println!("part 2: {}", location_ranges.into_iter().map(|ran| *ran.start()).min().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn example() -> Almanac {
+		input_parser().parse(include_str!("../inputs/day5_ex.txt")).unwrap()
+	}
+
+	#[test]
+	fn chain() {
+		let almanac = example();
+		let names = |chain: Vec<&Map>| chain.iter().map(|map| map.dst_cat.clone()).collect::<Vec<_>>();
+		assert_eq!(almanac.chain("seed", "location").map(|chain| chain.len()), Some(7));
+		assert_eq!(almanac.chain("soil", "light").map(names), Some(vec!["fertilizer".to_owned(), "water".to_owned(), "light".to_owned()]));
+		assert_eq!(almanac.chain("seed", "seed").map(|chain| chain.len()), Some(0));
+		assert!(almanac.chain("location", "seed").is_none());
+		assert!(almanac.chain("seed", "unknown").is_none());
+	}
+
+	#[test]
+	fn convert() {
+		let almanac = example();
+		// the statement's correspondences for seed 79
+		for (cat, value) in [("soil", 81), ("fertilizer", 81), ("water", 81), ("light", 74), ("temperature", 78), ("humidity", 78), ("location", 82)] {
+			assert_eq!(almanac.convert("seed", cat, 79), Some(vec![value]));
+		}
+		for (seed, location) in [(79, 82), (14, 43), (55, 86), (13, 35)] {
+			assert_eq!(almanac.convert("seed", "location", seed), Some(vec![location]));
+			assert!(almanac.convert("location", "seed", location).unwrap().contains(&seed));
+		}
+		assert_eq!(almanac.convert("seed", "unknown", 79), None);
+	}
+
+	#[test]
+	fn unmap_all_preimages() {
+		let map = Map { src_cat: "a".to_owned(), dst_cat: "b".to_owned(), ranges: vec![(7, 5, 1), (20, 10, 5)] };
+		// 7 comes from 5 through the range, and from itself through the identity
+		assert_eq!(map.unmap(7), [5, 7]);
+		// 5 is covered as a source, so nothing maps to it
+		assert_eq!(map.unmap(5), []);
+		assert_eq!(map.unmap(12), []);
+		assert_eq!(map.unmap(22), [12, 22]);
+
+		let almanac = input_parser().parse("seeds: 7 100\n\nseed-to-location map:\n7 5 1").unwrap();
+		assert_eq!(almanac.convert("location", "seed", 7), Some(vec![5, 7]));
+	}
 }
\ No newline at end of file
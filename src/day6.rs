@@ -1,27 +1,39 @@
 #![feature(trait_alias)]
 use chumsky::prelude::*;
 trait MyParser<T> = Parser<char, T, Error=Simple<char>>;
-fn int() -> impl MyParser<i64> {
+fn int() -> impl MyParser<u128> {
 	just(" ").repeated().ignore_then(text::int(10).from_str().unwrapped())
 }
 
-fn calculate_margin(time: i64, record: i64) -> i64 {
+fn calculate_margin(time: u128, record: u128) -> u128 {
 	// if we hold the button for N ms, we will travel D(N) = max(0,time-N) * N mm
 	// D(N) > record  <=>  time*N - N^2 > record  <=>  -N^2 + time*N - record > 0
-	let delta = time*time - 4*record;
-	if delta < 0 {
+	// D only grows up to time/2, and a distance too large for a u128 beats any record
+	let beats = |n: u128| n.checked_mul(time - n).map_or(true, |dist| dist > record);
+	let half = time / 2;
+	if !beats(half) {
 		return 0;
 	}
-	let sqrt_delta = (delta as f64).sqrt();
-	// <=>  N between (time +/- sqrt(Delta)) / 2
-	let min_hold = ((time as f64 - sqrt_delta) / 2.0).ceil() as i64;
-	let max_hold = ((time as f64 + sqrt_delta) / 2.0).floor() as i64;
-	max_hold - min_hold + 1
+	// look for the shortest winning hold between lo (losing) and hi (winning)
+	let (mut lo, mut hi) = (0, half);
+	// <=>  N strictly between (time +/- sqrt(Delta)) / 2
+	// when time^2 fits, isqrt puts the lower root within one of the estimate
+	// (Delta is positive, since D(time/2) > record)
+	if let Some(time_sq) = time.checked_mul(time) {
+		let delta = time_sq - 4*record;
+		let estimate = (time - delta.isqrt()) / 2;
+		(lo, hi) = (estimate.saturating_sub(1), (estimate + 1).min(half));
+	}
+	while hi - lo > 1 {
+		let mid = lo + (hi - lo) / 2;
+		if beats(mid) { hi = mid; } else { lo = mid; }
+	}
+	// D is symmetric around time/2, so max_hold = time - min_hold
+	time - 2*hi + 1
 }
 
-fn concat_ints(a: i64, b: i64) -> i64 {
-	assert!(a >= 0 && b >= 0);
-	a * 10i64.pow(b.ilog10() + 1) + b
+fn concat_ints(a: u128, b: u128) -> u128 {
+	a * 10u128.pow(b.ilog10() + 1) + b
 }
 
 fn main() {
@@ -40,4 +52,46 @@ fn main() {
 	let (time, record) = races.into_iter().reduce(|(t1,r1),(t2,r2)|
 		(concat_ints(t1,t2), concat_ints(r1,r2))).unwrap();
 	println!("part 2: {}", calculate_margin(time, record));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn brute_force_margin(time: u128, record: u128) -> u128 {
+		(0..=time).filter(|n| n * (time - n) > record).count() as u128
+	}
+
+	#[test]
+	fn matches_brute_force() {
+		// every record up to past the best distance, which includes
+		// all the records sitting exactly on an integer root
+		for time in 0..100 {
+			for record in 0..=time*time/4 + 1 {
+				assert_eq!(calculate_margin(time, record), brute_force_margin(time, record), "time {}, record {}", time, record);
+			}
+		}
+	}
+
+	#[test]
+	fn records_on_roots() {
+		let small = [7, 30, 1 << 40, (1 << 64) - 1].map(|time| (time, vec![1, 2, time / 3, (time - 1) / 2]));
+		// too large for time^2 to fit, with records which still do
+		let large = [(1 << 64, vec![1, 2, 1 << 20, 1 << 60]), (1 << 100, vec![1, 2, 1 << 20]), (u128::MAX, vec![1])];
+		for (time, holds) in small.into_iter().chain(large) {
+			for hold in holds {
+				let record = hold * (time - hold);
+				assert_eq!(calculate_margin(time, record), time - 2*hold - 1, "time {}, record {}", time, record);
+				assert_eq!(calculate_margin(time, record - 1), time - 2*hold + 1, "time {}, record {}", time, record - 1);
+			}
+		}
+	}
+
+	#[test]
+	fn large_values() {
+		assert_eq!(calculate_margin(10, u128::MAX), 0);
+		assert_eq!(calculate_margin(u128::MAX, 0), u128::MAX - 1);
+		// every hold but 0, 1 and their mirrors travels further than u128::MAX
+		assert_eq!(calculate_margin(u128::MAX, u128::MAX), u128::MAX - 3);
+	}
 }
\ No newline at end of file
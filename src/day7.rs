@@ -1,87 +1,139 @@
-#[derive(Eq, Ord)]
-struct Hand {
-	cards: [u8; 5],
-	bid: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+	HighCard,
+	OnePair,
+	TwoPair,
+	ThreeOfAKind,
+	FullHouse,
+	FourOfAKind,
+	FiveOfAKind,
 }
 
-impl Hand {
-	fn hand_type(&self) -> (u8, u8) {
-		let mut counts = [0u8; 13];
-		let mut jokers = 0;
-		for card in self.cards {
-			if card == 1 {
-				jokers += 1;
+struct Ruleset {
+	// card labels, from weakest to strongest
+	card_order: &'static [u8],
+	// cards which count as whatever card makes the hand strongest
+	wildcards: &'static [u8],
+	hand_size: usize,
+}
+
+static PART1: Ruleset = Ruleset { card_order: b"23456789TJQKA", wildcards: b"", hand_size: 5 };
+static PART2: Ruleset = Ruleset { card_order: b"J23456789TQKA", wildcards: b"J", hand_size: 5 };
+
+impl Ruleset {
+	fn card_value(&self, card: u8) -> u8 {
+		self.card_order.iter().position(|c| *c == card)
+			.unwrap_or_else(|| panic!("unknown card: {}", card as char)) as u8
+	}
+	fn hand_type(&self, cards: &[u8]) -> HandType {
+		let mut counts = vec![0u8; self.card_order.len()];
+		let mut wilds = 0;
+		for card in cards {
+			if self.wildcards.contains(card) {
+				wilds += 1;
 			} else {
-				counts[(card-2) as usize] += 1;
+				counts[self.card_value(*card) as usize] += 1;
 			}
 		}
-		counts.sort_unstable();
-		counts[12] += jokers;
-		(counts[12], counts[11])
-		/* (5,0): five of a kind
-		 * (4,1): four of a kind
-		 * (3,2): full house
-		 * (3,1): three of a kind
-		 * (2,2): two pair
-		 * (2,1): one pair
-		 * (1,1): high card
-		 */
+		counts.sort_unstable_by(|a, b| b.cmp(a));
+		counts.push(0);
+		// wildcards are always best used to grow the largest group
+		match (counts[0] + wilds, counts[1]) {
+			(5.., _) => HandType::FiveOfAKind,
+			(4, _) => HandType::FourOfAKind,
+			(3, 2..) => HandType::FullHouse,
+			(3, _) => HandType::ThreeOfAKind,
+			(2, 2..) => HandType::TwoPair,
+			(2, _) => HandType::OnePair,
+			_ => HandType::HighCard,
+		}
+	}
+}
+
+// Hands are ordered by type, then card by card, according to their ruleset.
+// Comparing hands from different rulesets is meaningless.
+struct Hand {
+	cards: Vec<u8>,
+	bid: u32,
+	key: (HandType, Vec<u8>),
+}
+impl Hand {
+	fn new(rules: &Ruleset, cards: &[u8], bid: u32) -> Self {
+		assert_eq!(cards.len(), rules.hand_size);
+		let key = (rules.hand_type(cards), cards.iter().map(|c| rules.card_value(*c)).collect());
+		Hand { cards: cards.to_vec(), bid, key }
 	}
 }
 impl PartialEq for Hand {
 	fn eq(&self, other: &Self) -> bool {
-		self.cards == other.cards
+		self.cmp(other).is_eq()
 	}
 }
+impl Eq for Hand {}
 impl PartialOrd for Hand {
 	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-		Some((self.hand_type(), self.cards).cmp(&(other.hand_type(), other.cards)))
+		Some(self.cmp(other))
+	}
+}
+impl Ord for Hand {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.key.cmp(&other.key)
 	}
 }
 
-fn main() {
-	let input = include_bytes!("../inputs/day7.txt");
+fn parse_input(input: &[u8]) -> Vec<(&[u8], u32)> {
 	let mut hands = vec![];
 	for line in input.split(|c| *c == b'\n') {
 		if line.is_empty() { continue; }
-		let mut cards = [0u8; 5];
-		for i in 0..5 {
-			let c = line[i];
-			cards[i] = match c {
-				b'2'..=b'9' => c - b'2' + 2,
-				b'T' => 10,
-				b'J' => 11,
-				b'Q' => 12,
-				b'K' => 13,
-				b'A' => 14,
-				_ => unreachable!()
-			};
-		}
-		assert_eq!(line[5], b' ');
-		let bid: u32 = std::str::from_utf8(&line[6..]).unwrap().parse().unwrap();
-		hands.push(Hand { cards, bid });
+		let sep = line.iter().position(|c| *c == b' ').unwrap();
+		let bid: u32 = std::str::from_utf8(&line[sep+1..]).unwrap().parse().unwrap();
+		hands.push((&line[..sep], bid));
 	}
+	hands
+}
 
+fn rank_hands(input: &[(&[u8], u32)], rules: &Ruleset) -> Vec<Hand> {
+	let mut hands: Vec<Hand> = input.iter().map(|(cards, bid)| Hand::new(rules, cards, *bid)).collect();
 	hands.sort();
+	hands
+}
+
+fn total_winnings(hands: &[Hand], explain: bool) -> u32 {
 	let mut score = 0;
 	for (i, hand) in hands.iter().enumerate() {
 		let rank = i + 1;
+		if explain {
+			println!("{:>5} {} {:<12} {:>4}", rank, std::str::from_utf8(&hand.cards).unwrap(),
+				format!("{:?}", hand.key.0), hand.bid);
+		}
 		score += (rank as u32) * hand.bid;
 	}
-	println!("part 1: {}", score);
+	score
+}
 
-	for hand in &mut hands {
-		for card in &mut hand.cards {
-			if *card == 11 { // Jack
-				*card = 1; // Joker
-			}
-		}
+fn main() {
+	let explain = std::env::args().any(|arg| arg == "--explain");
+	let input = parse_input(include_bytes!("../inputs/day7.txt"));
+	for (part, rules) in [(1, &PART1), (2, &PART2)] {
+		let hands = rank_hands(&input, rules);
+		println!("part {}: {}", part, total_winnings(&hands, explain));
 	}
-	hands.sort();
-	let mut score = 0;
-	for (i, hand) in hands.iter().enumerate() {
-		let rank = i + 1;
-		score += (rank as u32) * hand.bid;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn example_ranking() {
+		let example = parse_input(include_bytes!("../inputs/day7_ex.txt"));
+		for (rules, ranking, expected) in [
+			(&PART1, ["32T3K", "KTJJT", "KK677", "T55J5", "QQQJA"], 6440),
+			(&PART2, ["32T3K", "KK677", "T55J5", "QQQJA", "KTJJT"], 5905),
+		] {
+			let hands = rank_hands(&example, rules);
+			assert!(hands.iter().map(|hand| &hand.cards[..]).eq(ranking.map(str::as_bytes)));
+			assert_eq!(total_winnings(&hands, false), expected);
+		}
 	}
-	println!("part 2: {}", score);
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use chumsky::prelude::*;
+use util::cycle;
 
 fn node_id() -> impl Parser<char, u16, Error=Simple<char>> {
 	one_of("ABCDEFGHIJKLMNOPQRSTUVWXYZ").repeated().exactly(3)
@@ -51,12 +52,20 @@ fn main() {
 
 	let mut loop_sizes = vec![];
 	for start in start_nodes {
+		let walk_path = |node: &u16| {
+			let mut cur = *node;
+			for dir in &path {
+				cur = network.get(&cur).unwrap()[*dir as usize];
+			}
+			cur
+		};
+		let (loop_start, loop_len) = cycle::find_cycle(start, walk_path);
+		let loop_size = loop_len * path_len;
+
 		let mut final_states = vec![];
-		let mut visited = HashMap::new();
 		let mut cur = start;
 		let mut steps = 0;
-		while !visited.contains_key(&cur) {
-			visited.insert(cur, steps);
+		for _ in 0..loop_start + loop_len {
 			for i in 0..path_len {
 				cur = network.get(&cur).unwrap()[path[i] as usize];
 				steps += 1;
@@ -65,10 +74,8 @@ fn main() {
 				}
 			}
 		}
-		let loop_point = *visited.get(&cur).unwrap();
-		let loop_size = steps - loop_point;
 		// We now know all steps at which a final state is visited by this ghost:
-		// final_states[< loop_point], and then visited[>= loop_point] + k * loop_size
+		// final_states[< loop_start * path_len], and then final_states[>= loop_start * path_len] + k * loop_size
 
 		// We notice a pattern that will simplify the rest of the computation:
 		assert!(final_states.len() == 1);
@@ -0,0 +1,106 @@
+use std::{collections::HashMap, hash::Hash};
+
+// Both detectors return (mu, lambda): the sequence start, step(start), step(step(start))...
+// enters a cycle of length lambda after mu steps. The sequence must be eventually periodic.
+
+// Remembers every state; calls step exactly mu + lambda times
+pub fn find_cycle<S: Hash + Eq + Clone>(start: S, mut step: impl FnMut(&S) -> S) -> (usize, usize) {
+	let mut history = HashMap::new();
+	let mut state = start;
+	let mut i = 0;
+	loop {
+		if let Some(prev) = history.insert(state.clone(), i) {
+			return (prev, i - prev);
+		}
+		state = step(&state);
+		i += 1;
+	}
+}
+
+// Brent's algorithm: only keeps two states at a time, at the cost of more calls to step
+pub fn find_cycle_brent<S: Eq + Clone>(start: S, mut step: impl FnMut(&S) -> S) -> (usize, usize) {
+	// find lambda by moving the tortoise to the hare at every power of two
+	let mut power = 1;
+	let mut lambda = 1;
+	let mut tortoise = start.clone();
+	let mut hare = step(&start);
+	while tortoise != hare {
+		if power == lambda {
+			tortoise = hare.clone();
+			power *= 2;
+			lambda = 0;
+		}
+		hare = step(&hare);
+		lambda += 1;
+	}
+	// find mu by keeping the hare lambda steps ahead until they meet
+	let mut tortoise = start.clone();
+	let mut hare = start;
+	for _ in 0..lambda {
+		hare = step(&hare);
+	}
+	let mut mu = 0;
+	while tortoise != hare {
+		tortoise = step(&tortoise);
+		hare = step(&hare);
+		mu += 1;
+	}
+	(mu, lambda)
+}
+
+// State after n steps, skipping the full cycles after the first mu steps
+pub fn state_at<S>(start: S, mut step: impl FnMut(&S) -> S, n: u128, (mu, lambda): (usize, usize)) -> S {
+	let n = if n < mu as u128 {
+		n as usize
+	} else {
+		mu + ((n - mu as u128) % lambda as u128) as usize
+	};
+	let mut state = start;
+	for _ in 0..n {
+		state = step(&state);
+	}
+	state
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Sequences given by a successor table, starting from 0, with their (mu, lambda)
+	const TABLES: [(&[usize], (usize, usize)); 5] = [
+		(&[1, 2, 3, 4, 5, 3], (3, 3)),
+		(&[1, 2, 0], (0, 3)),        // mu = 0
+		(&[1, 2, 3, 3], (3, 1)),     // lambda = 1
+		(&[0], (0, 1)),              // mu = 0, lambda = 1
+		(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 1], (1, 9)),
+	];
+
+	#[test]
+	fn detectors_agree() {
+		for (table, expected) in TABLES {
+			let mut calls = 0;
+			assert_eq!(find_cycle(0, |x| { calls += 1; table[*x] }), expected);
+			assert_eq!(calls, expected.0 + expected.1);
+			assert_eq!(find_cycle_brent(0, |x| table[*x]), expected);
+		}
+		// a larger, non-tabulated sequence
+		let step = |x: &u64| (x * x + 1) % 1_000_003;
+		assert_eq!(find_cycle(2, step), find_cycle_brent(2, step));
+	}
+
+	#[test]
+	fn state_at_any_step() {
+		for (table, cycle) in TABLES {
+			let naive = |n: usize| (0..n).fold(0, |x, _| table[x]);
+			// before, at and after mu
+			for n in 0..30 {
+				assert_eq!(state_at(0, |x| table[*x], n as u128, cycle), naive(n));
+			}
+			let huge = 1_000_000_000_000_000_000_000_000_000u128;
+			let (mu, lambda) = cycle;
+			let reduced = mu + ((huge - mu as u128) % lambda as u128) as usize;
+			assert_eq!(state_at(0, |x| table[*x], huge, cycle), naive(reduced));
+		}
+	}
+}
\ No newline at end of file
@@ -1,3 +1,6 @@
+pub mod cycle;
+pub mod sequence;
+
 pub struct Grid<T> {
 	pub width: i32,
 	pub height: i32,
@@ -15,6 +18,12 @@ impl<T> Grid<T> {
 		}
 		Grid { width, height, data: data.into_boxed_slice() }
 	}
+	pub fn in_bounds(&self, pos: (i32, i32)) -> bool {
+		pos.0 >= 0 && pos.0 < self.width && pos.1 >= 0 && pos.1 < self.height
+	}
+	pub fn get(&self, pos: (i32, i32)) -> Option<&T> {
+		if self.in_bounds(pos) { Some(&self[pos]) } else { None }
+	}
 }
 impl<T: Clone> Grid<T> {
 	pub fn new(width: i32, height: i32, val: T) -> Self {
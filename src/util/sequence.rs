@@ -0,0 +1,99 @@
+// A polynomial sequence, stored in Newton forward-difference form:
+// f(n) = sum over k of diffs[k] * C(n, k), where diffs[k] = Δ^k f(0)
+pub struct Polynomial {
+	diffs: Vec<i128>,
+}
+impl Polynomial {
+	// Returns None if the sequence is not shown to be polynomial within its length,
+	// ie. its difference table never reaches a row of zeros
+	pub fn fit(values: &[i64]) -> Option<Self> {
+		let mut row: Vec<i128> = values.iter().map(|x| *x as i128).collect();
+		let mut diffs = vec![];
+		while !row.iter().all(|x| *x == 0) {
+			diffs.push(row[0]);
+			row = row.windows(2).map(|w| w[1] - w[0]).collect();
+			if row.is_empty() {
+				return None;
+			}
+		}
+		Some(Polynomial { diffs })
+	}
+	// Degree of the polynomial; the zero sequence is considered to be of degree 0
+	pub fn degree(&self) -> usize {
+		self.diffs.len().saturating_sub(1)
+	}
+	// Value at any index, including negative or far-off ones.
+	// Returns None if the result (or an intermediate binomial) overflows an i128.
+	pub fn eval(&self, n: i128) -> Option<i128> {
+		let mut res = 0i128;
+		let mut binom = 1i128; // C(n, k), generalized to negative n
+		for (k, diff) in self.diffs.iter().enumerate() {
+			if k > 0 {
+				// the product of k consecutive integers is divisible by k!, so this is exact
+				binom = binom.checked_mul(n - (k as i128 - 1))? / k as i128;
+			}
+			res = res.checked_add(diff.checked_mul(binom)?)?;
+		}
+		Some(res)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn day9_example() {
+		let mut next_sum = 0;
+		let mut prev_sum = 0;
+		for (values, next, prev) in [
+			([0, 3, 6, 9, 12, 15], 18, -3),
+			([1, 3, 6, 10, 15, 21], 28, 0),
+			([10, 13, 16, 21, 30, 45], 68, 5),
+		] {
+			let poly = Polynomial::fit(&values).unwrap();
+			assert_eq!(poly.eval(values.len() as i128), Some(next));
+			assert_eq!(poly.eval(-1), Some(prev));
+			next_sum += next;
+			prev_sum += prev;
+		}
+		assert_eq!((next_sum, prev_sum), (114, 2));
+	}
+
+	#[test]
+	fn degree_and_values() {
+		let squares = Polynomial::fit(&[0, 1, 4, 9, 16]).unwrap();
+		assert_eq!(squares.degree(), 2);
+		for n in -10..10 {
+			assert_eq!(squares.eval(n), Some(n * n));
+		}
+		let zero = Polynomial::fit(&[0, 0, 0]).unwrap();
+		assert_eq!((zero.degree(), zero.eval(1_000)), (0, Some(0)));
+		let constant = Polynomial::fit(&[7, 7]).unwrap();
+		assert_eq!((constant.degree(), constant.eval(-1_000)), (0, Some(7)));
+	}
+
+	#[test]
+	fn far_index() {
+		let n = 5 + 1_000_000_000_000;
+		assert_eq!(Polynomial::fit(&[0, 1, 4, 9, 16]).unwrap().eval(n), Some(n * n));
+		assert_eq!(Polynomial::fit(&[3, 5, 7]).unwrap().eval(n), Some(2 * n + 3));
+		assert_eq!(Polynomial::fit(&[0, 1, 8, 27, 64]).unwrap().eval(-n), Some(-n * n * n));
+	}
+
+	#[test]
+	fn not_polynomial() {
+		assert!(Polynomial::fit(&[1, 2, 4, 8, 16]).is_none());
+		// a cubic needs 5 values to show a row of zeros
+		assert!(Polynomial::fit(&[0, 1, 8, 27]).is_none());
+	}
+
+	#[test]
+	fn overflow() {
+		let cubes = Polynomial::fit(&[0, 1, 8, 27, 64]).unwrap();
+		assert_eq!(cubes.eval(10_000_000_000_000), None);
+		assert_eq!(cubes.eval(-10_000_000_000_000), None);
+		assert!(cubes.eval(1_000_000_000_000).is_some());
+	}
+}
\ No newline at end of file